@@ -165,7 +165,7 @@ pub async fn complete_structured(
                     template: r.data,
                     usage: r.usage,
                 })
-                .map_err(|e| Box::new(map_rstructor_error(e, model)) as Box<dyn Error>)
+                .map_err(|e| Box::new(map_rstructor_error(e, provider, model)) as Box<dyn Error>)
         }};
     }
 
@@ -214,7 +214,7 @@ pub async fn list_models(provider: &str) -> Result<Vec<String>, Box<dyn Error>>
 /// Uses rstructor's classified [`ApiErrorKind`] rather than matching on the
 /// error's display string, so each real-world failure (bad key, unknown model,
 /// oversized request, rate limit, ...) produces a specific, useful message.
-fn map_rstructor_error(err: RStructorError, model: &str) -> AiError {
+fn map_rstructor_error(err: RStructorError, provider_name: &str, model: &str) -> AiError {
     match &err {
         RStructorError::Timeout => AiError::Timeout,
         RStructorError::ApiError { provider, kind } => match kind {
@@ -228,6 +228,7 @@ fn map_rstructor_error(err: RStructorError, model: &str) -> AiError {
                 } else {
                     api_model.clone()
                 },
+                provider: provider_name.to_string(),
                 suggestion: suggestion.clone(),
             },
             ApiErrorKind::AuthenticationFailed => AiError::Auth {
@@ -279,9 +280,10 @@ pub enum AiError {
     #[error("API error: {code} {message}")]
     ApiError { code: u16, message: String },
 
-    #[error("Invalid model: {model}{}", .suggestion.as_ref().map(|s| format!(" (did you mean \"{s}\"?)")).unwrap_or_default())]
+    #[error("Invalid model: {model} for provider: {provider}{}\nCheck the provider's documentation for available models.", .suggestion.as_ref().map(|s| format!(" (did you mean \"{s}\"?)")).unwrap_or_default())]
     InvalidModel {
         model: String,
+        provider: String,
         suggestion: Option<String>,
     },
 
@@ -305,6 +307,23 @@ pub enum AiError {
     Other(String),
 }
 
+impl AiError {
+    /// Stable machine-readable identifier, used by `--output json`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AiError::ProviderNotFound { .. } => "provider_not_found",
+            AiError::ProviderNotAvailable { .. } => "provider_not_available",
+            AiError::ApiError { .. } => "api_error",
+            AiError::InvalidModel { .. } => "invalid_model",
+            AiError::Auth { .. } => "auth_failed",
+            AiError::RequestTooLarge => "request_too_large",
+            AiError::RateLimited { .. } => "rate_limited",
+            AiError::Timeout => "timeout",
+            AiError::Other(_) => "provider_error",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,9 +382,14 @@ mod tests {
                 suggestion: Some("gemini-3.5-flash".to_string()),
             },
         );
-        match map_rstructor_error(err, "gemini-bogus") {
-            AiError::InvalidModel { model, suggestion } => {
+        match map_rstructor_error(err, "gemini", "gemini-bogus") {
+            AiError::InvalidModel {
+                model,
+                provider,
+                suggestion,
+            } => {
                 assert_eq!(model, "gemini-bogus");
+                assert_eq!(provider, "gemini");
                 assert_eq!(suggestion.as_deref(), Some("gemini-3.5-flash"));
             }
             other => panic!("expected InvalidModel, got {other:?}"),
@@ -377,6 +401,7 @@ mod tests {
         assert!(matches!(
             map_rstructor_error(
                 RStructorError::api_error("OpenAI", ApiErrorKind::AuthenticationFailed),
+                "openai",
                 "m"
             ),
             AiError::Auth { .. }
@@ -384,12 +409,13 @@ mod tests {
         assert!(matches!(
             map_rstructor_error(
                 RStructorError::api_error("OpenAI", ApiErrorKind::RequestTooLarge),
+                "openai",
                 "m"
             ),
             AiError::RequestTooLarge
         ));
         assert!(matches!(
-            map_rstructor_error(RStructorError::Timeout, "m"),
+            map_rstructor_error(RStructorError::Timeout, "openai", "m"),
             AiError::Timeout
         ));
     }
@@ -398,10 +424,13 @@ mod tests {
     fn test_invalid_model_message_includes_suggestion() {
         let e = AiError::InvalidModel {
             model: "foo".to_string(),
+            provider: "openai".to_string(),
             suggestion: Some("bar".to_string()),
         };
         let s = e.to_string();
         assert!(s.contains("foo"), "message: {s}");
+        assert!(s.contains("for provider: openai"), "message: {s}");
+        assert!(s.contains("documentation"), "message: {s}");
         assert!(s.contains("bar"), "message: {s}");
     }
 }
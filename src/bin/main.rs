@@ -4,14 +4,14 @@ use cmt::config_mod::{file as config_file, Config};
 use cmt::pricing::{self, PricingCache};
 use cmt::template_mod::TemplateManager;
 use cmt::{
-    append_to_cmtignore, create_commit, generate_commit_message, get_current_branch,
-    get_readme_excerpt, load_cmtignore, Args, CommitError, CommitOptions, Spinner,
+    append_to_cmtignore, Args, CmtEngine, CmtError, Command, CommitError, CommitOptions,
+    CommitReport, DiffSource, ErrorReport, FixupReport, FixupTarget, IgnoreCommand, JsonReport,
+    OutputFormat, Server, SignMode, Spinner, UsageReport,
 };
 use colored::*;
 use dotenv::dotenv;
-use git2::Repository;
 use std::io::{self, IsTerminal, Write};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::{env, process};
 
 enum CommitAction {
@@ -140,26 +140,18 @@ fn present_and_copy(
     println!("{}", "Commit message:".green().bold());
     println!("{}", message);
 
-    // Use actual token counts from the API, or estimate (~4 chars/token).
-    let (input, output) = match (input_tokens, output_tokens) {
-        (Some(i), Some(o)) => (i, o),
-        _ => (
-            ((diff_len + recent_len) as u64) / 4,
-            (message.len() as u64) / 4,
-        ),
-    };
-    let cost_str = pricing_cache
-        .get_model_pricing(provider, model)
-        .and_then(|p| pricing::calculate_cost(&p, input, output))
+    let usage = UsageReport::new(input_tokens, output_tokens, diff_len + recent_len, message);
+    let cost_str = usage
+        .cost_usd(pricing_cache, provider, model)
         .map(|c| format!(", {}", pricing::format_cost(c)))
         .unwrap_or_default();
-    let prefix = if input_tokens.is_some() { "" } else { "~" };
+    let prefix = if usage.estimated { "~" } else { "" };
     println!(
         "{}",
         format!(
             "{}{} tokens, {:.1}s{}",
             prefix,
-            input + output,
+            usage.input_tokens + usage.output_tokens,
             elapsed.as_secs_f32(),
             cost_str
         )
@@ -171,6 +163,134 @@ fn present_and_copy(
     }
 }
 
+/// Report a fatal error and exit(1): a typed `{"error": ...}` document on
+/// stdout in JSON mode, otherwise a red heading plus the error on stderr.
+fn fail(json: bool, heading: &str, err: &(dyn std::error::Error + 'static)) -> ! {
+    if json {
+        println!("{}", ErrorReport::from_error(err).to_json());
+    } else {
+        eprintln!("{}", heading.red().bold());
+        eprintln!("{}", err);
+    }
+    process::exit(1);
+}
+
+/// Offer (or, with -y, make) a `fixup!` commit for `target`. Returns false when
+/// the user asks for a new message instead.
+fn run_fixup(
+    engine: &CmtEngine,
+    target: &FixupTarget,
+    options: &CommitOptions,
+    args: &Args,
+    json: bool,
+    interactive: bool,
+    no_commit: bool,
+) -> bool {
+    let config = engine.config();
+    let message = match engine.fixup_message(target) {
+        Ok(message) => message,
+        Err(e) => fail(json, "Error:", &e),
+    };
+
+    if json {
+        let commit = if args.yes && !no_commit {
+            match engine.commit(&message, options) {
+                Ok(created) => Some(CommitReport { oid: created.oid }),
+                Err(e) => fail(json, "Error creating commit:", &e),
+            }
+        } else {
+            None
+        };
+        let report = FixupReport {
+            target: &target.id,
+            subject: &target.subject,
+            message: &message,
+            matched_lines: target.matched_lines,
+            total_lines: target.total_lines,
+            commit,
+        };
+        match serde_json::to_string(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => fail(true, "Error serializing report:", &e),
+        }
+        return true;
+    }
+    if config.message_only {
+        println!("{}", message);
+        return true;
+    }
+
+    if target.total_lines > 0 {
+        println!(
+            "{}",
+            format!(
+                "Looks like a follow-up to {} \"{}\" ({} of {} changed lines were last touched by it).",
+                target.short_id(),
+                target.subject,
+                target.matched_lines,
+                target.total_lines
+            )
+            .cyan()
+        );
+    }
+    println!("{}", "Commit message:".green().bold());
+    println!("{}", message);
+    if no_commit {
+        return true;
+    }
+
+    if !args.yes {
+        if !interactive {
+            eprintln!(
+                "{}",
+                "Not committing: stdin is not a terminal. Re-run with -y to commit, or --no-commit to just print the message."
+                    .yellow()
+            );
+            return true;
+        }
+        print!(
+            "{}",
+            "[f]ixup commit, [m]essage to write a new one instead, [n]o to cancel: ".cyan()
+        );
+        let _ = io::stdout().flush();
+        let mut input = String::new();
+        let _ = io::stdin().read_line(&mut input);
+        match input.trim().to_lowercase().as_str() {
+            "f" | "fixup" | "y" | "yes" => {}
+            "m" | "message" => return false,
+            _ => {
+                println!("{}", "Commit cancelled.".yellow());
+                return true;
+            }
+        }
+    }
+
+    match engine.commit(&message, options) {
+        Ok(created) => println!(
+            "{}",
+            format!("✓ Created commit: {}", &created.oid[..7])
+                .green()
+                .bold()
+        ),
+        Err(e) => fail(false, "Error creating commit:", &e),
+    }
+    true
+}
+
+/// Create the commit, or for `cmt squash --commit`, replace the branch's
+/// commits since `squash_base` with it.
+fn commit_or_squash(
+    engine: &CmtEngine,
+    squash_base: Option<&str>,
+    message: &str,
+    options: &CommitOptions,
+) -> Result<cmt::CommitResult, CmtError> {
+    match squash_base {
+        Some(base) => engine.squash_commit(base, message, options),
+        None => engine.commit(message, options),
+    }
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok(); // Load .env file if it exists
@@ -310,13 +430,11 @@ async fn main() {
     }
 
     // Initialize template manager (only needed for --list-templates and commit generation)
+    let json = args.output == OutputFormat::Json;
+
     let template_manager = match TemplateManager::new() {
         Ok(manager) => manager,
-        Err(e) => {
-            eprintln!("{}", "Error initializing templates:".red().bold());
-            eprintln!("{}", e);
-            process::exit(1);
-        }
+        Err(e) => fail(json, "Error initializing templates:", &e),
     };
 
     // Handle listing available templates
@@ -345,47 +463,135 @@ async fn main() {
     let cli_config = Config::from_args(&args);
     config.merge(&cli_config);
 
+    // Long-running JSON-RPC server for editor plugins
+    if let Some(Command::Serve { socket }) = &args.command {
+        let mut server = match Server::new(config, ".") {
+            Ok(server) => server,
+            Err(e) => fail(json, "Error starting server:", &e),
+        };
+        let result = match socket {
+            #[cfg(unix)]
+            Some(path) => server.serve_unix(path).await,
+            #[cfg(not(unix))]
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "--socket requires a Unix platform; use stdio instead",
+            )),
+            None => server.serve_stdio().await,
+        };
+        if let Err(e) = result {
+            fail(json, "Error running server:", &e);
+        }
+        process::exit(0);
+    }
+
     // Only prompt / animate when both stdin and stdout are real terminals. When
     // piped or run in CI, cmt must not block on a closed stdin or read EOF and
     // silently cancel; it relies on flags (-y / --no-commit / -m) instead.
-    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    // JSON mode is always non-interactive: stdout is reserved for the document.
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal() && !json;
+    // Suppress progress and decoration when stdout carries only the message/JSON.
+    let quiet = config.message_only || json;
 
-    // Open git repository (discover searches up the directory tree)
-    let repo = match Repository::discover(".") {
-        Ok(repo) => repo,
-        Err(e) => {
-            eprintln!("{}", "Error opening git repository:".red().bold());
-            eprintln!("{}", e);
-            process::exit(1);
+    // An external diff (--diff-file / --stdin-diff) is described as-is: it
+    // doesn't need a repository and is never committed.
+    let patch = if args.stdin_diff {
+        let mut buf = Vec::new();
+        match io::Read::read_to_end(&mut io::stdin(), &mut buf) {
+            Ok(_) => Some(buf),
+            Err(e) => fail(json, "Error reading diff from stdin:", &e),
+        }
+    } else if let Some(path) = &args.diff_file {
+        match std::fs::read(path) {
+            Ok(buf) => Some(buf),
+            Err(e) => fail(json, &format!("Error reading {}:", path.display()), &e),
         }
+    } else {
+        None
+    };
+    // Only staged changes can be committed; any other source is describe-only.
+    // `cmt squash <base>` describes base..HEAD and commits only with --commit.
+    let source = args.diff_source();
+    let (squash_base, squash_commit) = match &args.command {
+        Some(Command::Squash { base, commit }) => (Some(base.clone()), *commit),
+        _ => (None, false),
     };
+    if squash_base.is_some() && (patch.is_some() || source != DiffSource::Staged) {
+        let err = CmtError::Squash(
+            "squash describes base..HEAD; it can't be combined with --diff-file, --stdin-diff, --unstaged or --from"
+                .to_string(),
+        );
+        fail(json, "Error:", &err);
+    }
+    let no_commit = args.no_commit
+        || patch.is_some()
+        || source != DiffSource::Staged
+        || (squash_base.is_some() && !squash_commit);
 
-    // Get repository root for .cmtignore
-    let repo_root = repo.workdir().unwrap_or_else(|| std::path::Path::new("."));
-
-    // Load .cmtignore patterns
-    let cmtignore_patterns = load_cmtignore(repo_root);
-
-    // Get staged changes (includes both diff text and stats in one pass).
-    // Read from the resolved `config` (defaults < global < project < CLI), not
-    // raw `args`, so .cmt.toml settings actually take effect.
-    let get_staged = || {
-        cmt::get_staged_changes(
-            &repo,
-            config.context_lines,
-            config.max_lines_per_file,
-            config.max_line_width,
-            config.max_file_lines,
-            &cmtignore_patterns,
-        )
+    // Open the repository (discover searches up the directory tree) and resolve
+    // repository-dependent config: prompt files, trailers, .cmtignore.
+    let mut engine = match CmtEngine::builder()
+        .config(config)
+        .template_manager(template_manager)
+        .repo_path(".")
+        .repo_optional(patch.is_some())
+        .build()
+    {
+        Ok(engine) => engine,
+        Err(e @ CmtError::Git(_)) => fail(json, "Error opening git repository:", &e),
+        Err(e) => fail(json, "Error loading configuration:", &e),
     };
+    // `cmt ignore check <path>`: explain the rule that matches, if any
+    if let Some(Command::Ignore {
+        command: IgnoreCommand::Check { path },
+    }) = &args.command
+    {
+        let reason = match engine.check_ignore(path) {
+            Ok(reason) => reason,
+            Err(e) => fail(json, "Error:", &e),
+        };
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "path": path.display().to_string(),
+                    "ignored": reason.is_some(),
+                    "reason": reason,
+                })
+            );
+        } else {
+            match &reason {
+                Some(reason) => println!("{}: {}", path.display(), reason),
+                None => println!("{}: not ignored", path.display()),
+            }
+        }
+        process::exit(if reason.is_some() { 0 } else { 1 });
+    }
+
+    let config = engine.config().clone();
+    let repo_root = engine
+        .repo()
+        .ok()
+        .and_then(|repo| repo.workdir())
+        .unwrap_or_else(|| std::path::Path::new("."))
+        .to_path_buf();
 
-    let staged = match get_staged() {
-        Ok(changes) => changes,
-        Err(e) if e.to_string().contains("No changes have been staged") => {
+    // Get the changes (includes both diff text and stats in one pass).
+    let collected = match (&patch, &squash_base) {
+        (Some(patch), _) => engine.collect_patch(patch),
+        (None, Some(base)) => engine.collect_squash(base),
+        (None, None) => engine.collect_changes(&source),
+    };
+    let diff = match collected {
+        Ok(diff) => diff,
+        Err(CmtError::NothingStaged) => {
             // Nothing staged — offer to stage tracked changes instead of
             // dead-ending (the most common first-run frustration).
-            let unstaged = cmt::has_unstaged_changes(&repo);
+            let repo = match engine.repo() {
+                Ok(repo) => repo,
+                Err(e) => fail(json, "Error:", &e),
+            };
+            let unstaged = cmt::has_unstaged_changes(repo);
             let do_stage = if args.all {
                 unstaged
             } else if unstaged && interactive && !args.yes {
@@ -402,19 +608,21 @@ async fn main() {
             };
 
             if do_stage {
-                if let Err(err) = cmt::stage_tracked_changes(&repo) {
-                    eprintln!("{}", "Error staging changes:".red().bold());
-                    eprintln!("{}", err);
-                    process::exit(1);
+                if let Err(err) = cmt::stage_tracked_changes(repo) {
+                    fail(json, "Error staging changes:", &err);
                 }
-                match get_staged() {
-                    Ok(changes) => changes,
-                    Err(err) => {
-                        eprintln!("{}", "Error:".red().bold());
-                        eprintln!("{}", err);
-                        process::exit(1);
-                    }
+                match engine.collect_diff() {
+                    Ok(diff) => diff,
+                    Err(err) => fail(json, "Error:", &err),
                 }
+            } else if json {
+                let message = if unstaged {
+                    "No changes have been staged for commit; stage them, or re-run with -a/--all."
+                } else {
+                    "No changes have been staged for commit."
+                };
+                println!("{}", ErrorReport::new("nothing_staged", message).to_json());
+                process::exit(1);
             } else {
                 eprintln!("{}", "No changes have been staged for commit.".red().bold());
                 if unstaged {
@@ -426,16 +634,16 @@ async fn main() {
                 process::exit(1);
             }
         }
-        Err(e) => {
-            eprintln!("{}", "Error:".red().bold());
-            eprintln!("{}", e);
-            process::exit(1);
-        }
+        Err(e) => fail(json, "Error:", &e),
     };
 
+    if squash_base.is_some() {
+        engine.credit_squashed_authors(&diff);
+    }
+
     // Handle files that exceed the threshold (prompt to add to .cmtignore).
     // Only when interactive — never block a piped/CI run on this prompt.
-    if !staged.stats.skipped_files.is_empty() && interactive && !args.yes && !config.message_only {
+    if !diff.stats.skipped_files.is_empty() && interactive && !args.yes && !quiet {
         println!();
         println!(
             "{}",
@@ -446,14 +654,14 @@ async fn main() {
             .yellow()
             .bold()
         );
-        for (file, adds, dels) in &staged.stats.skipped_files {
-            let total = adds + dels;
+        for file in &diff.stats.skipped_files {
+            let total = file.insertions + file.deletions;
             let lines_display = if total >= 1000 {
                 format!("{}K lines", total / 1000)
             } else {
                 format!("{} lines", total)
             };
-            println!("  - {} ({})", file, lines_display);
+            println!("  - {} ({})", file.path, lines_display);
         }
         println!();
 
@@ -472,14 +680,14 @@ async fn main() {
         };
 
         if should_add {
-            let files_to_add: Vec<String> = staged
+            let files_to_add: Vec<String> = diff
                 .stats
                 .skipped_files
                 .iter()
-                .map(|(f, _, _)| f.clone())
+                .map(|f| f.path.clone())
                 .collect();
 
-            match append_to_cmtignore(repo_root, &files_to_add) {
+            match append_to_cmtignore(&repo_root, &files_to_add) {
                 Ok(()) => {
                     println!(
                         "{}",
@@ -505,87 +713,157 @@ async fn main() {
         }
     }
 
-    // Scrub likely secrets from the diff before it ever leaves the machine.
-    let staged_changes = if config.redact {
-        let (scrubbed, redacted) = cmt::redact_secrets(&staged.diff_text);
-        if redacted > 0 && !config.message_only {
-            eprintln!(
+    // --fixup: a follow-up to a recent unpushed commit is committed as
+    // `fixup! <subject>` for a later `git rebase --autosquash`. Nothing goes
+    // to the model, so this comes before the redaction notices and review.
+    if let Some(fixup) = &args.fixup {
+        let target = match fixup {
+            Some(rev) => engine.resolve_fixup_target(rev).map(Some),
+            None => engine.find_fixup_target(),
+        };
+        match target {
+            Ok(Some(target)) => {
+                let options = CommitOptions {
+                    no_verify: args.no_verify,
+                    sign: SignMode::from_config(&config),
+                };
+                if run_fixup(
+                    &engine,
+                    &target,
+                    &options,
+                    &args,
+                    json,
+                    interactive,
+                    no_commit,
+                ) {
+                    process::exit(0);
+                }
+            }
+            Ok(None) if !quiet => eprintln!(
                 "{}",
                 format!(
-                    "🔒 Redacted {} likely secret{} from the diff before sending it to the model (disable with --no-redact).",
-                    redacted,
-                    if redacted == 1 { "" } else { "s" }
+                    "No unpushed commit last touched {}% of the staged lines; writing a new message instead.",
+                    config.fixup_threshold
                 )
                 .yellow()
-            );
+            ),
+            Ok(None) => {}
+            Err(e) => fail(json, "Error finding a fixup target:", &e),
         }
-        scrubbed
-    } else {
-        staged.diff_text.clone()
-    };
-
-    // Determine diff size for adaptive behaviors (very high thresholds - Gemini supports 1M tokens)
-    let is_very_large_diff = staged.stats.files_changed > 150
-        || (staged.stats.insertions + staged.stats.deletions) > 50000;
+    }
 
-    // Get recent commits - only skip for extremely large diffs
-    let include_recent = config.include_recent_commits && !is_very_large_diff;
-    let effective_recent_count = if include_recent {
-        config.recent_commits_count // Always use full count - we have the token budget
-    } else {
-        0
-    };
+    // Secrets were scrubbed from the diff before it ever leaves the machine.
+    if diff.redactions > 0 && !quiet {
+        eprintln!(
+            "{}",
+            format!(
+                "🔒 Redacted {} likely secret{} from the diff before sending it to the model (disable with --no-redact).",
+                diff.redactions,
+                if diff.redactions == 1 { "" } else { "s" }
+            )
+            .yellow()
+        );
+        if !config.secrets.review_redactions {
+            for finding in &diff.findings {
+                eprintln!("  {}", finding.to_string().dimmed());
+            }
+        }
+    }
 
-    if config.include_recent_commits && !include_recent {
+    if diff.pii > 0 && !quiet {
         eprintln!(
             "{}",
-            "Skipping recent commits for this extremely large diff.".yellow()
+            format!(
+                "🔒 Replaced {} personal data value{} with placeholders ([pii] in .cmt.toml).",
+                diff.pii,
+                if diff.pii == 1 { "" } else { "s" }
+            )
+            .yellow()
         );
     }
 
-    let recent_commits = if include_recent {
-        match cmt::get_recent_commits(&repo, effective_recent_count) {
-            Ok(commits) => commits,
-            Err(e) => {
-                eprintln!(
-                    "{}",
-                    "Warning: Failed to get recent commits:".yellow().bold()
-                );
-                eprintln!("{}", e);
-                String::new()
-            }
+    // --review-redactions: nothing is sent until the redactions are confirmed.
+    if config.secrets.review_redactions && diff.redactions > 0 {
+        if !interactive {
+            fail(
+                json,
+                "Error:",
+                &CmtError::SecretsFound(diff.findings.clone()),
+            );
         }
-    } else {
-        String::new()
-    };
+        eprintln!("{}", "Redacted before sending:".bold());
+        for finding in &diff.findings {
+            eprintln!("  {}", finding);
+        }
+        eprint!(
+            "{}",
+            format!("Send the redacted diff to {}? [y/N] ", config.provider).cyan()
+        );
+        let _ = io::stderr().flush();
+        let mut input = String::new();
+        let send = io::stdin().read_line(&mut input).is_ok()
+            && matches!(input.trim().to_lowercase().as_str(), "y" | "yes");
+        if !send {
+            eprintln!("{}", "Nothing was sent to the model.".yellow());
+            process::exit(1);
+        }
+    }
 
-    // Get current branch name for context
-    let branch_name = get_current_branch(&repo);
+    // Merges, reverts and cherry-picks get git's conventional message shape.
+    if let Some(op) = diff.operation.as_ref().filter(|_| !quiet) {
+        eprintln!(
+            "{}",
+            format!("Concluding the {}; using git's message conventions.", op).cyan()
+        );
+    }
 
-    // Get README excerpt for project context (first 50 lines)
-    let readme_excerpt = get_readme_excerpt(&repo, 50);
+    if config.include_recent_commits && diff.stats.is_very_large() {
+        eprintln!(
+            "{}",
+            "Skipping recent commits for this extremely large diff.".yellow()
+        );
+    }
 
     // Show raw diff if requested
-    if config.show_raw_diff {
+    if config.show_raw_diff && !json {
         println!("{}", "Raw diff:".cyan().bold());
-        println!("{}", staged_changes);
+        println!("{}", diff.diff);
         println!();
     }
 
+    // Print the assembled prompt and stop before any model call
+    if args.show_prompt {
+        match engine.build_prompt(&diff) {
+            Ok(prompt) if json => {
+                println!(
+                    "{}",
+                    serde_json::json!({ "system": prompt.system, "user": prompt.user })
+                );
+                process::exit(0);
+            }
+            Ok(prompt) => {
+                println!("{}", "System prompt:".cyan().bold());
+                println!("{}", prompt.system);
+                println!();
+                println!("{}", "User prompt:".cyan().bold());
+                println!("{}", prompt.user);
+                process::exit(0);
+            }
+            Err(e) => fail(json, "Error building prompt:", &e),
+        }
+    }
+
     // Get model info for display
-    let model_name = config
-        .model
-        .clone()
-        .unwrap_or_else(|| default_model(&config.provider).to_string());
+    let model_name = engine.model();
 
     // Show diff stats before sending to LLM (unless message-only mode)
-    if !config.message_only && !config.no_diff_stats {
-        staged.stats.print();
+    if !quiet && !config.no_diff_stats {
+        diff.stats.print();
     }
 
     // Generate commit message with spinner (only when attached to a terminal;
     // don't animate into a pipe/log).
-    let spinner = if !config.message_only && io::stdout().is_terminal() {
+    let spinner = if !quiet && io::stdout().is_terminal() {
         Some(Spinner::new(&format!(
             "Generating commit message with {}...",
             model_name
@@ -595,16 +873,7 @@ async fn main() {
     };
 
     let start_time = Instant::now();
-    let result = match generate_commit_message(
-        &config,
-        &staged_changes,
-        &recent_commits,
-        branch_name.as_deref(),
-        readme_excerpt.as_deref(),
-        &template_manager,
-    )
-    .await
-    {
+    let result = match engine.generate(&diff).await {
         Ok(result) => {
             if let Some(s) = &spinner {
                 s.finish_and_clear();
@@ -615,12 +884,57 @@ async fn main() {
             if let Some(s) = &spinner {
                 s.finish_and_clear();
             }
-            eprintln!("{}", "Error generating commit message:".red().bold());
-            eprintln!("{}", e);
-            process::exit(1);
+            fail(json, "Error generating commit message:", &e);
         }
     };
     let elapsed = start_time.elapsed();
+
+    if json {
+        // Commit only when explicitly asked (-y); there is no prompt to confirm on.
+        let commit = if args.yes && !no_commit {
+            let options = CommitOptions {
+                no_verify: args.no_verify,
+                sign: SignMode::from_config(&config),
+            };
+            match commit_or_squash(&engine, squash_base.as_deref(), &result.message, &options) {
+                Ok(created) => Some(CommitReport { oid: created.oid }),
+                Err(e) => fail(json, "Error creating commit:", &e),
+            }
+        } else {
+            None
+        };
+
+        let usage = UsageReport::new(
+            result.input_tokens,
+            result.output_tokens,
+            diff.diff.len() + diff.recent_commits.len(),
+            &result.message,
+        );
+        // The pricing fetch usually finished during generation; give it a moment if not.
+        pricing_cache.wait_get(Duration::from_secs(2));
+        let cost_usd = usage.cost_usd(&mut pricing_cache, &config.provider, &model_name);
+
+        let report = JsonReport {
+            template: &result.template,
+            message: &result.message,
+            stats: &diff.stats,
+            redactions: diff.redactions,
+            secrets: &diff.findings,
+            pii: diff.pii,
+            provider: &config.provider,
+            model: &model_name,
+            usage,
+            cost_usd,
+            elapsed_ms: elapsed.as_millis(),
+            commit,
+        };
+        match serde_json::to_string(&report) {
+            Ok(doc) => println!("{}", doc),
+            Err(e) => fail(json, "Error serializing output:", &e),
+        }
+        process::exit(0);
+    }
+
     let commit_message = result.message;
 
     // Output the commit message
@@ -640,24 +954,21 @@ async fn main() {
             &config.provider,
             &model_name,
             &mut pricing_cache,
-            staged_changes.len(),
-            recent_commits.len(),
+            diff.diff.len(),
+            diff.recent_commits.len(),
             args.copy,
         );
 
         // Handle commit prompt (default behavior unless --no-commit)
-        if !args.no_commit && !args.yes && !interactive {
+        if !no_commit && !args.yes && !interactive {
             // No TTY to confirm on, and -y was not passed: don't silently cancel.
             eprintln!(
                 "{}",
                 "Not committing: stdin is not a terminal. Re-run with -y to commit, or --no-commit to just print the message."
                     .yellow()
             );
-        } else if !args.no_commit {
+        } else if !no_commit {
             let mut current_message = commit_message.clone();
-            // Clone the resolved config so hint regeneration can layer in a hint
-            // without mutating the original.
-            let mut current_config = config.clone();
             // Hints accumulate across regenerations so a later hint refines
             // rather than erases an earlier one.
             let mut hints: Vec<String> = Vec::new();
@@ -693,8 +1004,14 @@ async fn main() {
                         // Create the commit using git commit (respects hooks)
                         let options = CommitOptions {
                             no_verify: args.no_verify,
+                            sign: SignMode::from_config(&config),
                         };
-                        match create_commit(&repo, &current_message, &options) {
+                        match commit_or_squash(
+                            &engine,
+                            squash_base.as_deref(),
+                            &current_message,
+                            &options,
+                        ) {
                             Ok(result) => {
                                 println!(
                                     "{}",
@@ -703,7 +1020,7 @@ async fn main() {
                                         .bold()
                                 );
                             }
-                            Err(err @ CommitError::PreCommitFailed { .. }) => {
+                            Err(CmtError::Commit(err @ CommitError::PreCommitFailed { .. })) => {
                                 eprintln!("{}", "Pre-commit hook failed.".red().bold());
                                 if let Some(output) = err.hook_output() {
                                     eprintln!();
@@ -712,7 +1029,7 @@ async fn main() {
                                 eprintln!("{}", "Use --no-verify (-n) to skip hooks.".yellow());
                                 process::exit(1);
                             }
-                            Err(err @ CommitError::CommitMsgFailed { .. }) => {
+                            Err(CmtError::Commit(err @ CommitError::CommitMsgFailed { .. })) => {
                                 eprintln!("{}", "Commit-msg hook failed.".red().bold());
                                 if let Some(output) = err.hook_output() {
                                     eprintln!();
@@ -721,6 +1038,23 @@ async fn main() {
                                 eprintln!("{}", "Use --no-verify (-n) to skip hooks.".yellow());
                                 process::exit(1);
                             }
+                            Err(CmtError::Commit(CommitError::SigningFailed { output })) => {
+                                eprintln!("{}", "Commit signing failed.".red().bold());
+                                if !output.trim().is_empty() {
+                                    eprintln!();
+                                    eprintln!("{}", output.trim());
+                                }
+                                let err = CommitError::SigningFailed { output };
+                                if let Some(hint) = err.signing_hint() {
+                                    eprintln!();
+                                    eprintln!("{}", format!("Hint: {}", hint).yellow());
+                                }
+                                eprintln!(
+                                    "{}",
+                                    "Use --no-gpg-sign to commit without a signature.".yellow()
+                                );
+                                process::exit(1);
+                            }
                             Err(e) => {
                                 eprintln!("{}", "Error creating commit:".red().bold());
                                 eprintln!("{}", e);
@@ -759,22 +1093,13 @@ async fn main() {
                             if !hint.is_empty() {
                                 // Accumulate hints so they compound across rounds.
                                 hints.push(hint.to_string());
-                                current_config.hint = Some(hints.join("; "));
+                                engine.config_mut().hint = Some(hints.join("; "));
 
                                 // Regenerate with spinner
                                 let regen_start = Instant::now();
                                 let spinner =
                                     Spinner::new(&format!("Regenerating with {}...", model_name));
-                                match generate_commit_message(
-                                    &current_config,
-                                    &staged_changes,
-                                    &recent_commits,
-                                    branch_name.as_deref(),
-                                    readme_excerpt.as_deref(),
-                                    &template_manager,
-                                )
-                                .await
-                                {
+                                match engine.generate(&diff).await {
                                     Ok(new_result) => {
                                         spinner.finish_and_clear();
                                         current_message = new_result.message.clone();
@@ -789,8 +1114,8 @@ async fn main() {
                                             &config.provider,
                                             &model_name,
                                             &mut pricing_cache,
-                                            staged_changes.len(),
-                                            recent_commits.len(),
+                                            diff.diff.len(),
+                                            diff.recent_commits.len(),
                                             args.copy,
                                         );
                                     }
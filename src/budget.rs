@@ -0,0 +1,353 @@
+//! Token budget for the diff sent to the model.
+//!
+//! Diff text is measured in estimated tokens. When the shown files don't fit
+//! the budget (by default a share of the selected model's input window), the
+//! packer keeps the most important files first: source over tests over docs
+//! over fixtures, and within each group the smallest, most focused changes.
+//! A file that doesn't fit whole keeps as many of its hunks as do. Whatever
+//! was cut is reported so the model (and the user) know the diff is partial.
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Rough characters per token for code and prose, as in the usage estimate.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Share (percent) of the model's input window the diff may use; the rest is
+/// left for the system prompt, README, recent commits and estimation error.
+const DIFF_SHARE_OF_WINDOW: u64 = 75;
+
+/// Input window assumed when the model's isn't known: no pricing metadata
+/// cached yet, or a model LiteLLM doesn't list.
+pub const DEFAULT_INPUT_TOKENS: u64 = 128_000;
+
+/// Tokens set aside for the note appended to a trimmed file.
+const NOTE_TOKENS: usize = 16;
+
+/// Estimated token count of `text` (~4 characters per token).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The diff budget for a model accepting `max_input_tokens`.
+pub fn diff_budget(max_input_tokens: u64) -> usize {
+    (max_input_tokens * DIFF_SHARE_OF_WINDOW / 100) as usize
+}
+
+/// What the packer left out to fit the budget.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BudgetReport {
+    pub max_tokens: usize,
+    /// Estimated tokens of the diff text that was kept
+    pub used_tokens: usize,
+    pub cut_files: Vec<CutFile>,
+}
+
+/// A file whose diff was trimmed or left out entirely.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CutFile {
+    pub path: String,
+    pub hunks_omitted: usize,
+    pub hunks_total: usize,
+}
+
+impl CutFile {
+    /// Whether none of the file's diff made it in.
+    pub fn is_dropped(&self) -> bool {
+        self.hunks_omitted == self.hunks_total
+    }
+
+    /// `left out` or `3 of 5 hunks left out`
+    pub fn describe(&self) -> String {
+        if self.is_dropped() {
+            "left out".to_string()
+        } else {
+            format!(
+                "{} of {} hunks left out",
+                self.hunks_omitted, self.hunks_total
+            )
+        }
+    }
+}
+
+/// How much a file's diff says about the change, most telling first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Source,
+    Test,
+    Docs,
+    Fixture,
+}
+
+const FIXTURE_DIRS: &[&str] = &[
+    "fixtures",
+    "fixture",
+    "__fixtures__",
+    "testdata",
+    "test-data",
+    "test_data",
+    "__snapshots__",
+    "snapshots",
+    "golden",
+    "mocks",
+    "__mocks__",
+];
+const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "specs", "e2e"];
+const DOC_DIRS: &[&str] = &["doc", "docs"];
+const DOC_EXTENSIONS: &[&str] = &["md", "markdown", "rst", "txt", "adoc"];
+
+fn role(path: &str) -> Role {
+    let path = Path::new(path);
+    let dirs: Vec<String> = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    let in_dir = |names: &[&str]| dirs.iter().any(|dir| names.contains(&dir.as_str()));
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = name.split('.').next().unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if in_dir(FIXTURE_DIRS) || extension == "snap" {
+        Role::Fixture
+    } else if in_dir(TEST_DIRS)
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_spec")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+    {
+        Role::Test
+    } else if in_dir(DOC_DIRS) || DOC_EXTENSIONS.contains(&extension.as_str()) {
+        Role::Docs
+    } else {
+        Role::Source
+    }
+}
+
+/// One file's diff: the header lines, then one entry per `@@` hunk.
+struct FileDiff<'a> {
+    path: &'a str,
+    header: &'a str,
+    hunks: Vec<&'a str>,
+    tokens: usize,
+}
+
+impl<'a> FileDiff<'a> {
+    fn split(path: &'a str, text: &'a str) -> Self {
+        let mut starts: Vec<usize> = Vec::new();
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            if line.starts_with("@@") {
+                starts.push(offset);
+            }
+            offset += line.len();
+        }
+        let header_end = starts.first().copied().unwrap_or(text.len());
+        let hunks = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| &text[start..starts.get(i + 1).copied().unwrap_or(text.len())])
+            .collect();
+        Self {
+            path,
+            header: &text[..header_end],
+            hunks,
+            tokens: estimate_tokens(text),
+        }
+    }
+}
+
+/// Fit per-file diff sections (`(path, text)`, in diff order) into
+/// `max_tokens`, keeping the output in diff order. The report is `None` when
+/// everything fit.
+pub(crate) fn pack(
+    sections: &[(String, String)],
+    max_tokens: usize,
+) -> (String, Option<BudgetReport>) {
+    let files: Vec<FileDiff> = sections
+        .iter()
+        .map(|(path, text)| FileDiff::split(path, text))
+        .collect();
+    let total: usize = files.iter().map(|f| f.tokens).sum();
+    if total <= max_tokens {
+        return (
+            sections.iter().map(|(_, text)| text.as_str()).collect(),
+            None,
+        );
+    }
+
+    // Kept hunk indices per file; `None` means the whole file is kept.
+    let mut kept: Vec<Option<Vec<usize>>> = vec![Some(Vec::new()); files.len()];
+    let mut order: Vec<usize> = (0..files.len()).collect();
+    order.sort_by_key(|&i| (role(files[i].path), files[i].tokens, files[i].path));
+
+    let mut remaining = max_tokens;
+    for i in order {
+        let file = &files[i];
+        if file.tokens <= remaining {
+            remaining -= file.tokens;
+            kept[i] = None;
+            continue;
+        }
+        let overhead = estimate_tokens(file.header) + NOTE_TOKENS;
+        if file.hunks.is_empty() || overhead >= remaining {
+            continue;
+        }
+        let mut budget = remaining - overhead;
+        let mut by_size: Vec<usize> = (0..file.hunks.len()).collect();
+        by_size.sort_by_key(|&h| (estimate_tokens(file.hunks[h]), h));
+        let mut hunks = Vec::new();
+        for h in by_size {
+            let tokens = estimate_tokens(file.hunks[h]);
+            if tokens <= budget {
+                budget -= tokens;
+                hunks.push(h);
+            }
+        }
+        if !hunks.is_empty() {
+            hunks.sort_unstable();
+            remaining = budget;
+            kept[i] = Some(hunks);
+        }
+    }
+
+    let mut text = String::new();
+    let mut cut_files = Vec::new();
+    for (file, kept) in files.iter().zip(kept) {
+        let Some(hunks) = kept else {
+            text.push_str(file.header);
+            text.extend(file.hunks.iter().copied());
+            continue;
+        };
+        let hunks_total = file.hunks.len().max(1);
+        cut_files.push(CutFile {
+            path: file.path.to_string(),
+            hunks_omitted: hunks_total - hunks.len(),
+            hunks_total,
+        });
+        if hunks.is_empty() {
+            continue;
+        }
+        text.push_str(file.header);
+        for h in &hunks {
+            text.push_str(file.hunks[*h]);
+        }
+        text.push_str(&format!(
+            "\n[Note: {} of {} hunks omitted to fit the token budget.]\n",
+            hunks_total - hunks.len(),
+            hunks_total
+        ));
+    }
+
+    let used_tokens = estimate_tokens(&text);
+    (
+        text,
+        Some(BudgetReport {
+            max_tokens,
+            used_tokens,
+            cut_files,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(path: &str, hunks: &[&str]) -> (String, String) {
+        let mut text = format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", path);
+        for (i, body) in hunks.iter().enumerate() {
+            text.push_str(&format!("@@ -{0},1 +{0},1 @@\n{1}", i * 10 + 1, body));
+        }
+        (path.to_string(), text)
+    }
+
+    #[test]
+    fn test_role() {
+        assert_eq!(role("src/git.rs"), Role::Source);
+        assert_eq!(role("tests/integration.rs"), Role::Test);
+        assert_eq!(role("pkg/server_test.go"), Role::Test);
+        assert_eq!(role("web/app.spec.ts"), Role::Test);
+        assert_eq!(role("src/FooTest.java"), Role::Test);
+        assert_eq!(role("README.md"), Role::Docs);
+        assert_eq!(role("tests/fixtures/big.json"), Role::Fixture);
+        assert_eq!(role("src/__snapshots__/view.snap"), Role::Fixture);
+    }
+
+    #[test]
+    fn test_everything_fits() {
+        let sections = vec![section("src/a.rs", &["-a\n+b\n"])];
+        let (text, report) = pack(&sections, 1000);
+        assert_eq!(text, sections[0].1);
+        assert!(report.is_none());
+    }
+
+    #[test]
+    fn test_source_is_kept_before_tests_and_fixtures() {
+        let big = "+x\n".repeat(100);
+        let sections = vec![
+            section("tests/fixtures/data.json", &[&big]),
+            section("tests/api.rs", &[&big]),
+            section("src/api.rs", &[&big]),
+        ];
+        let budget = estimate_tokens(&sections[1].1) + estimate_tokens(&sections[2].1);
+        let (text, report) = pack(&sections, budget);
+        let report = report.unwrap();
+
+        assert!(text.contains("b/src/api.rs"));
+        assert!(text.contains("b/tests/api.rs"));
+        assert!(!text.contains("fixtures"));
+        // Diff order is preserved for what was kept
+        assert!(text.find("tests/api.rs").unwrap() < text.find("src/api.rs").unwrap());
+        assert_eq!(
+            report.cut_files,
+            vec![CutFile {
+                path: "tests/fixtures/data.json".to_string(),
+                hunks_omitted: 1,
+                hunks_total: 1,
+            }]
+        );
+        assert!(report.used_tokens <= report.max_tokens);
+    }
+
+    #[test]
+    fn test_large_file_keeps_its_smallest_hunks() {
+        let big = "+x\n".repeat(200);
+        let sections = vec![section(
+            "src/lib.rs",
+            &[&big, "+small one\n", "+small two\n"],
+        )];
+        let (text, report) = pack(&sections, 100);
+        let report = report.unwrap();
+
+        assert!(text.contains("+small one"));
+        assert!(text.contains("+small two"));
+        assert!(!text.contains("+x\n"));
+        assert!(text.contains("[Note: 1 of 3 hunks omitted to fit the token budget.]"));
+        assert_eq!(report.cut_files[0].describe(), "1 of 3 hunks left out");
+    }
+
+    #[test]
+    fn test_packing_is_deterministic() {
+        let sections: Vec<_> = (0..20)
+            .map(|i| section(&format!("src/m{}.rs", i), &[&"+y\n".repeat(i * 7 + 3)]))
+            .collect();
+        let first = pack(&sections, 300);
+        let second = pack(&sections, 300);
+        assert_eq!(first.0, second.0);
+        assert_eq!(first.1, second.1);
+    }
+}
@@ -1,8 +1,20 @@
 //! .cmtignore file support for excluding files from commit message generation
-
+//!
+//! `.cmtignore` files follow `.gitignore` syntax and precedence exactly:
+//! negation with `!`, directory-only patterns with a trailing `/`, anchoring
+//! with a leading or inner `/`, basename matching otherwise, character classes
+//! and `**`. A `.cmtignore` in a subdirectory applies to paths below it and
+//! takes precedence over the ones above, and a file inside an ignored
+//! directory can't be re-included.
+
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
 
 use crate::config::defaults::CMTIGNORE_FILENAME;
 
@@ -62,7 +74,165 @@ pub fn append_to_cmtignore(repo_root: &Path, files: &[String]) -> io::Result<()>
     Ok(())
 }
 
-/// Check if a file path matches a .cmtignore pattern.
+/// Every ignore list for a repository: personal lists (the global ignore file
+/// and the `ignore` config key) and the repository's `.cmtignore` files, read
+/// on demand (the root one, and a nested one for each directory a matched
+/// path passes through).
+///
+/// Each personal list is evaluated on its own, before the repository's files,
+/// and a path any of them ignores stays ignored: a `!` pattern in a
+/// `.cmtignore` can't re-include it.
+#[derive(Debug, Default)]
+pub struct Cmtignore {
+    root: Option<PathBuf>,
+    /// Personal lists with the label their reasons are shown under
+    lists: Vec<(String, Gitignore)>,
+    /// Patterns given directly, below every file in precedence
+    base: Option<Gitignore>,
+    /// Parsed `.cmtignore` per directory (`""` is the root), `None` if absent
+    files: Mutex<HashMap<String, Option<Arc<Gitignore>>>>,
+}
+
+impl Cmtignore {
+    /// The `.cmtignore` files under `repo_root`.
+    pub fn load(repo_root: &Path) -> Self {
+        Self {
+            root: Some(repo_root.to_path_buf()),
+            ..Self::default()
+        }
+    }
+
+    /// Root-level patterns only, with no files read (e.g. for a patch).
+    pub fn from_patterns(patterns: &[String]) -> Self {
+        Self {
+            base: build(Path::new(""), patterns.iter().map(String::as_str)),
+            ..Self::default()
+        }
+    }
+
+    /// Add a personal list read from `path` (gitignore syntax, patterns
+    /// relative to the repository root), if it exists.
+    pub fn with_file(self, label: &str, path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => self.with_lines(label, content.lines()),
+            Err(_) => self,
+        }
+    }
+
+    /// Add a personal list of patterns.
+    pub fn with_patterns(self, label: &str, patterns: &[String]) -> Self {
+        self.with_lines(label, patterns.iter().map(String::as_str))
+    }
+
+    fn with_lines<'a>(mut self, label: &str, lines: impl IntoIterator<Item = &'a str>) -> Self {
+        if let Some(gitignore) = build(Path::new(""), lines) {
+            self.lists.push((label.to_string(), gitignore));
+        }
+        self
+    }
+
+    /// Why `path` (relative to the repository root, `/`-separated) is
+    /// ignored: the list or file and the pattern that matched, e.g.
+    /// `.cmtignore: *.sql`, `web/.cmtignore: /generated/` or
+    /// `~/.config/cmt/ignore: *.pem`.
+    pub fn reason(&self, path: &str) -> Option<String> {
+        let path = path.replace('\\', "/");
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+
+        self.lists
+            .iter()
+            .find_map(|(label, gitignore)| {
+                excluded(&components, |components, is_dir| {
+                    decide(gitignore, label, &components.join("/"), is_dir)
+                })
+            })
+            .or_else(|| {
+                excluded(&components, |components, is_dir| {
+                    self.decide(components, is_dir)
+                })
+            })
+    }
+
+    /// The deepest `.cmtignore` with an opinion on `components`: `Some(Some)`
+    /// when ignored, `Some(None)` when re-included with `!`, `None` when no
+    /// pattern matched.
+    fn decide(&self, components: &[&str], is_dir: bool) -> Option<Option<String>> {
+        for depth in (0..components.len()).rev() {
+            let dir = components[..depth].join("/");
+            let Some(gitignore) = self.file(&dir) else {
+                continue;
+            };
+            let label = match dir.as_str() {
+                "" => CMTIGNORE_FILENAME.to_string(),
+                dir => format!("{}/{}", dir, CMTIGNORE_FILENAME),
+            };
+            let decision = decide(&gitignore, &label, &components[depth..].join("/"), is_dir);
+            if decision.is_some() {
+                return decision;
+            }
+        }
+        decide(
+            self.base.as_ref()?,
+            CMTIGNORE_FILENAME,
+            &components.join("/"),
+            is_dir,
+        )
+    }
+
+    /// The parsed `.cmtignore` in `dir`, read once.
+    fn file(&self, dir: &str) -> Option<Arc<Gitignore>> {
+        let root = self.root.as_ref()?;
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files
+            .entry(dir.to_string())
+            .or_insert_with(|| {
+                let dir_path = root.join(dir);
+                let content = fs::read_to_string(dir_path.join(CMTIGNORE_FILENAME)).ok()?;
+                build(&dir_path, content.lines()).map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+/// Apply git's rule for one ignore source: a path is ignored when one of its
+/// parent directories is (a file in an ignored directory can't be
+/// re-included), or else when the path itself is.
+fn excluded(
+    components: &[&str],
+    decide: impl Fn(&[&str], bool) -> Option<Option<String>>,
+) -> Option<String> {
+    for depth in 1..components.len() {
+        if let Some(Some(reason)) = decide(&components[..depth], true) {
+            return Some(reason);
+        }
+    }
+    decide(components, false).flatten()
+}
+
+/// One matcher's verdict on `path`, labelled `label: pattern` when ignored.
+fn decide(gitignore: &Gitignore, label: &str, path: &str, is_dir: bool) -> Option<Option<String>> {
+    match gitignore.matched(path, is_dir) {
+        Match::Ignore(glob) => Some(Some(format!("{}: {}", label, glob.original()))),
+        Match::Whitelist(_) => Some(None),
+        Match::None => None,
+    }
+}
+
+/// A matcher for gitignore-syntax `lines`, or `None` when none are patterns.
+/// Invalid lines are skipped, as git does.
+fn build<'a>(root: &Path, lines: impl IntoIterator<Item = &'a str>) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for line in lines {
+        let _ = builder.add_line(None, line);
+    }
+    builder
+        .build()
+        .ok()
+        .filter(|gitignore| !gitignore.is_empty())
+}
+
+/// Check if a file path matches a simple glob (as used by skip rules; the
+/// `.cmtignore` files themselves go through [`Cmtignore`]).
 ///
 /// Patterns are matched component-by-component (split on `/`):
 /// - `*` matches any run of characters within a single path component (not `/`)
@@ -252,4 +422,169 @@ mod tests {
         assert!(matches_pattern("src/x/y.tsx", "src/**/*.tsx"));
         assert!(!matches_pattern("src-gen/y.tsx", "src/**/*.tsx"));
     }
+
+    fn ignored(cmtignore: &Cmtignore, path: &str) -> bool {
+        cmtignore.reason(path).is_some()
+    }
+
+    #[test]
+    fn test_gitignore_edge_cases() {
+        let patterns: Vec<String> = [
+            "*.log",
+            "!keep.log",
+            "/root-only.txt",
+            "build/",
+            "docs/*.md",
+            "!docs/README.md",
+            "schema[0-9].sql",
+            "**/cache/**",
+            "tmp/",
+            "!tmp/kept.txt",
+        ]
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+        let cmtignore = Cmtignore::from_patterns(&patterns);
+
+        // Unanchored patterns match the basename at any depth; `!` re-includes
+        assert!(ignored(&cmtignore, "debug.log"));
+        assert!(ignored(&cmtignore, "a/b/debug.log"));
+        assert!(!ignored(&cmtignore, "a/keep.log"));
+        // A leading `/` anchors to the root
+        assert!(ignored(&cmtignore, "root-only.txt"));
+        assert!(!ignored(&cmtignore, "sub/root-only.txt"));
+        // A trailing `/` matches directories (and so everything inside) only
+        assert!(ignored(&cmtignore, "build/out.js"));
+        assert!(ignored(&cmtignore, "web/build/out.js"));
+        assert!(!ignored(&cmtignore, "src/build"));
+        // An inner `/` anchors too, and `*` doesn't cross directories
+        assert!(ignored(&cmtignore, "docs/guide.md"));
+        assert!(!ignored(&cmtignore, "docs/README.md"));
+        assert!(!ignored(&cmtignore, "docs/api/guide.md"));
+        assert!(!ignored(&cmtignore, "web/docs/guide.md"));
+        // Character classes
+        assert!(ignored(&cmtignore, "schema1.sql"));
+        assert!(!ignored(&cmtignore, "schemaX.sql"));
+        assert!(ignored(&cmtignore, "a/cache/b/c.bin"));
+        // A file in an excluded directory can't be re-included
+        assert!(ignored(&cmtignore, "tmp/kept.txt"));
+
+        assert_eq!(
+            cmtignore.reason("a/b/debug.log").as_deref(),
+            Some(".cmtignore: *.log")
+        );
+    }
+
+    #[test]
+    fn test_nested_cmtignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("web/generated")).unwrap();
+        fs::write(root.join(CMTIGNORE_FILENAME), "*.sql\n*.gen.ts\n").unwrap();
+        fs::write(
+            root.join("web").join(CMTIGNORE_FILENAME),
+            "!*.gen.ts\n/generated/\nlocal.sql\n",
+        )
+        .unwrap();
+        let cmtignore = Cmtignore::load(root);
+
+        assert_eq!(
+            cmtignore.reason("query.sql").as_deref(),
+            Some(".cmtignore: *.sql")
+        );
+        assert_eq!(
+            cmtignore.reason("api.gen.ts").as_deref(),
+            Some(".cmtignore: *.gen.ts")
+        );
+        // The deeper file wins, and its patterns are relative to its directory
+        assert_eq!(cmtignore.reason("web/api.gen.ts"), None);
+        assert_eq!(
+            cmtignore.reason("web/generated/client.ts").as_deref(),
+            Some("web/.cmtignore: /generated/")
+        );
+        assert_eq!(cmtignore.reason("generated/client.ts"), None);
+        assert_eq!(
+            cmtignore.reason("web/local.sql").as_deref(),
+            Some("web/.cmtignore: local.sql")
+        );
+        assert_eq!(
+            cmtignore.reason("web/other.sql").as_deref(),
+            Some(".cmtignore: *.sql")
+        );
+    }
+
+    #[test]
+    fn test_matches_git_for_the_same_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let repo = git2::Repository::init(root).unwrap();
+        fs::create_dir_all(root.join("pkg")).unwrap();
+        let top =
+            "*.tmp\n!important.tmp\n/dist\nlogs/\n[Dd]ebug?.txt\nvendor/**/*.js\n\\#literal\n!keep/\n";
+        let nested = "!*.tmp\nlocal/\n";
+        for name in [".gitignore", CMTIGNORE_FILENAME] {
+            fs::write(root.join(name), top).unwrap();
+            fs::write(root.join("pkg").join(name), nested).unwrap();
+        }
+        let cmtignore = Cmtignore::load(root);
+
+        for path in [
+            "a.tmp",
+            "x/important.tmp",
+            "pkg/a.tmp",
+            "dist/app.js",
+            "src/dist/app.js",
+            "logs/today.txt",
+            "src/logs",
+            "Debug1.txt",
+            "debugX.txt",
+            "debug10.txt",
+            "vendor/lib.js",
+            "vendor/a/b/lib.js",
+            "vendor/lib.ts",
+            "#literal",
+            "pkg/local/file.rs",
+            "keep/a.tmp",
+            "local/file.rs",
+            "src/main.rs",
+        ] {
+            assert_eq!(
+                ignored(&cmtignore, path),
+                repo.is_path_ignored(path).unwrap(),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_personal_lists_cannot_be_reincluded() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let global = root.join("global-ignore");
+        fs::write(&global, "*.pem\nsecrets/\n").unwrap();
+        fs::write(root.join(CMTIGNORE_FILENAME), "!*.pem\n*.sql\n").unwrap();
+        let cmtignore = Cmtignore::load(root)
+            .with_file("~/.config/cmt/ignore", &global)
+            .with_file("missing", &root.join("does-not-exist"))
+            .with_patterns("ignore (config)", &["*.dump".to_string()]);
+
+        assert_eq!(
+            cmtignore.reason("certs/server.pem").as_deref(),
+            Some("~/.config/cmt/ignore: *.pem")
+        );
+        assert_eq!(
+            cmtignore.reason("app/secrets/key.txt").as_deref(),
+            Some("~/.config/cmt/ignore: secrets/")
+        );
+        assert_eq!(
+            cmtignore.reason("db/prod.dump").as_deref(),
+            Some("ignore (config): *.dump")
+        );
+        assert_eq!(
+            cmtignore.reason("db/seed.sql").as_deref(),
+            Some(".cmtignore: *.sql")
+        );
+        assert_eq!(cmtignore.reason("src/main.rs"), None);
+    }
 }
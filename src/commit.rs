@@ -8,6 +8,8 @@ use std::io::Write;
 use std::process::Command;
 use tempfile::NamedTempFile;
 
+use crate::config::Config;
+
 /// Errors that can occur when creating a commit.
 #[derive(Debug)]
 pub enum CommitError {
@@ -15,6 +17,8 @@ pub enum CommitError {
     PreCommitFailed { output: String },
     /// The commit-msg hook failed.
     CommitMsgFailed { output: String },
+    /// Signing the commit (`-S`) failed: missing agent, unknown key, etc.
+    SigningFailed { output: String },
     /// A general git error occurred.
     GitError(String),
     /// Failed to create or write to the temp file.
@@ -28,6 +32,10 @@ impl std::fmt::Display for CommitError {
         match self {
             CommitError::PreCommitFailed { .. } => write!(f, "pre-commit hook failed"),
             CommitError::CommitMsgFailed { .. } => write!(f, "commit-msg hook failed"),
+            CommitError::SigningFailed { output } => match self.signing_hint() {
+                Some(hint) => write!(f, "commit signing failed: {}", hint),
+                None => write!(f, "commit signing failed: {}", output.trim()),
+            },
             CommitError::GitError(msg) => write!(f, "git error: {}", msg),
             CommitError::TempFileError(e) => write!(f, "temp file error: {}", e),
             CommitError::ParseError => write!(f, "failed to parse commit output"),
@@ -38,6 +46,18 @@ impl std::fmt::Display for CommitError {
 impl std::error::Error for CommitError {}
 
 impl CommitError {
+    /// Stable machine-readable identifier, used by `--output json`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CommitError::PreCommitFailed { .. } => "pre_commit_failed",
+            CommitError::CommitMsgFailed { .. } => "commit_msg_failed",
+            CommitError::SigningFailed { .. } => "signing_failed",
+            CommitError::GitError(_) => "git_error",
+            CommitError::TempFileError(_) => "temp_file_error",
+            CommitError::ParseError => "parse_error",
+        }
+    }
+
     /// Return captured hook output when available.
     pub fn hook_output(&self) -> Option<&str> {
         match self {
@@ -52,6 +72,65 @@ impl CommitError {
             _ => None,
         }
     }
+
+    /// An actionable suggestion for a signing failure, based on what gpg or
+    /// ssh-keygen reported.
+    pub fn signing_hint(&self) -> Option<&'static str> {
+        let CommitError::SigningFailed { output } = self else {
+            return None;
+        };
+        let lower = output.to_lowercase();
+        let hint = if lower.contains("cannot run gpg") || lower.contains("cannot run ssh-keygen") {
+            "the signing program was not found; install it or set gpg.program / gpg.ssh.program"
+        } else if lower.contains("inappropriate ioctl")
+            || lower.contains("pinentry")
+            || lower.contains("no agent running")
+            || lower.contains("can't connect to the agent")
+        {
+            "gpg-agent could not ask for the passphrase; run `export GPG_TTY=$(tty)` and retry"
+        } else if lower.contains("agent refused operation")
+            || lower.contains("could not open a connection to your authentication agent")
+        {
+            "ssh-agent does not hold the signing key; add it with `ssh-add`"
+        } else if lower.contains("couldn't load public key") || lower.contains("no such file") {
+            "the SSH signing key was not found; check that user.signingkey points to your public key"
+        } else if lower.contains("no secret key")
+            || lower.contains("secret key not available")
+            || lower.contains("unusable secret key")
+        {
+            "no secret key for the signing key; check user.signingkey (or the key passed to -S) against `gpg --list-secret-keys`"
+        } else {
+            return None;
+        };
+        Some(hint)
+    }
+}
+
+/// How the commit should be signed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum SignMode {
+    /// Defer to git config (`commit.gpgSign`, `user.signingKey`).
+    #[default]
+    Config,
+    /// Sign (`-S`), optionally with a specific key id.
+    Sign(Option<String>),
+    /// Never sign (`--no-gpg-sign`), even if git config asks to.
+    NoSign,
+}
+
+impl SignMode {
+    /// Map the resolved `gpg_sign` / `signing_key` config onto git's
+    /// `-S` / `--no-gpg-sign`. Setting `signing_key` implies signing unless
+    /// `gpg_sign = false`.
+    pub fn from_config(config: &Config) -> Self {
+        match config.gpg_sign {
+            Some(true) => SignMode::Sign(config.signing_key.clone()),
+            Some(false) => SignMode::NoSign,
+            // A configured key means "sign with this key"
+            None if config.signing_key.is_some() => SignMode::Sign(config.signing_key.clone()),
+            None => SignMode::Config,
+        }
+    }
 }
 
 /// Options for creating a commit.
@@ -59,6 +138,8 @@ impl CommitError {
 pub struct CommitOptions {
     /// Skip pre-commit and commit-msg hooks.
     pub no_verify: bool,
+    /// Commit signing behavior.
+    pub sign: SignMode,
 }
 
 /// Result of a successful commit.
@@ -106,6 +187,19 @@ pub fn create_commit(
         cmd.arg("--no-verify");
     }
 
+    match &options.sign {
+        SignMode::Config => {}
+        SignMode::Sign(Some(key)) => {
+            cmd.arg(format!("--gpg-sign={}", key));
+        }
+        SignMode::Sign(None) => {
+            cmd.arg("--gpg-sign");
+        }
+        SignMode::NoSign => {
+            cmd.arg("--no-gpg-sign");
+        }
+    }
+
     // Run the command
     let output = cmd
         .output()
@@ -117,6 +211,14 @@ pub fn create_commit(
         let combined = format!("{}{}", stdout, stderr);
         let combined_trimmed = combined.trim().to_string();
 
+        // Signing failures exit 128 and would otherwise surface as a generic
+        // git error; hooks exit 1, so their output never counts.
+        if is_signing_failure(&combined, output.status.code()) {
+            return Err(CommitError::SigningFailed {
+                output: combined_trimmed,
+            });
+        }
+
         // Check for hook failures.
         if let Some(failure_kind) = detect_hook_failure(&combined, output.status.code()) {
             return match failure_kind {
@@ -166,6 +268,27 @@ fn parse_commit_hash(output: &str) -> Option<String> {
     None
 }
 
+/// Whether `git commit` failed because the commit couldn't be signed: git's
+/// own messages for a failed or missing signing program, on its fatal exit.
+fn is_signing_failure(output: &str, exit_code: Option<i32>) -> bool {
+    if exit_code != Some(128) {
+        return false;
+    }
+    let lower = output.to_lowercase();
+    let names_signer = ["gpg", "ssh-keygen", "gpgsm"]
+        .iter()
+        .any(|program| lower.contains(program));
+    lower.contains("gpg failed to sign the data")
+        || [
+            "cannot run gpg",
+            "cannot run ssh-keygen",
+            "cannot run gpgsm",
+        ]
+        .iter()
+        .any(|message| lower.contains(message))
+        || (lower.contains("failed to write commit object") && names_signer)
+}
+
 fn detect_hook_failure(output: &str, exit_code: Option<i32>) -> Option<HookFailureKind> {
     // Git typically exits with code 1 for hook failures.
     if exit_code != Some(1) {
@@ -303,6 +426,107 @@ mod tests {
         assert_eq!(err.hook_output(), None);
     }
 
+    #[test]
+    fn test_is_signing_failure() {
+        let fatal = Some(128);
+        assert!(is_signing_failure(
+            "error: gpg failed to sign the data\nfatal: failed to write commit object",
+            fatal
+        ));
+        assert!(is_signing_failure(
+            "error: cannot run gpg: No such file or directory",
+            fatal
+        ));
+        assert!(is_signing_failure(
+            "error: Couldn't load public key ~/.ssh/id.pub: No such file or directory?\n\nfatal: failed to write commit object\nerror: ssh-keygen failed to sign",
+            fatal
+        ));
+        assert!(!is_signing_failure("pre-commit hook failed", Some(1)));
+        // A hook talking about signing is still a hook failure
+        assert!(!is_signing_failure(
+            "check-signatures: run ssh-keygen -Y sign first\ngpg failed to sign the data",
+            Some(1)
+        ));
+        // A fatal error that has nothing to do with signing
+        assert!(!is_signing_failure(
+            "fatal: failed to write commit object: No space left on device",
+            fatal
+        ));
+    }
+
+    #[test]
+    fn test_sign_mode_from_config() {
+        let mode = |gpg_sign, key: Option<&str>| {
+            SignMode::from_config(&Config {
+                gpg_sign,
+                signing_key: key.map(str::to_string),
+                ..Config::default()
+            })
+        };
+        assert_eq!(mode(None, None), SignMode::Config);
+        assert_eq!(mode(Some(true), None), SignMode::Sign(None));
+        assert_eq!(
+            mode(None, Some("ABCD")),
+            SignMode::Sign(Some("ABCD".to_string()))
+        );
+        assert_eq!(mode(Some(false), Some("ABCD")), SignMode::NoSign);
+    }
+
+    #[test]
+    fn test_signing_hint_is_actionable() {
+        let err = CommitError::SigningFailed {
+            output: "gpg: signing failed: Inappropriate ioctl for device".to_string(),
+        };
+        assert!(err.signing_hint().unwrap().contains("GPG_TTY"));
+
+        let err = CommitError::SigningFailed {
+            output: "gpg: skipped \"ABCD\": No secret key".to_string(),
+        };
+        assert!(err.signing_hint().unwrap().contains("--list-secret-keys"));
+        assert!(err.to_string().contains("no secret key"));
+
+        let err = CommitError::SigningFailed {
+            output: "something unexpected".to_string(),
+        };
+        assert_eq!(err.signing_hint(), None);
+        assert!(err.to_string().contains("something unexpected"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_commit_reports_signing_failure() {
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let repo_path = temp_dir.path();
+
+        run_git(repo_path, &["init"]);
+        run_git(repo_path, &["config", "user.name", "Test User"]);
+        run_git(repo_path, &["config", "user.email", "test@example.com"]);
+        // A signing program that always fails, like a missing key would.
+        run_git(repo_path, &["config", "gpg.program", "false"]);
+
+        fs::write(repo_path.join("file.txt"), "content\n").expect("failed to write staged file");
+        run_git(repo_path, &["add", "file.txt"]);
+
+        let repo = Repository::open(repo_path).expect("failed to open test repo");
+        let options = CommitOptions {
+            sign: SignMode::Sign(None),
+            ..CommitOptions::default()
+        };
+        let error = create_commit(&repo, "test commit", &options).expect_err("expected failure");
+        assert!(
+            matches!(error, CommitError::SigningFailed { .. }),
+            "expected signing failure, got {error:?}"
+        );
+
+        // --no-gpg-sign wins over commit.gpgSign in git config.
+        run_git(repo_path, &["config", "commit.gpgSign", "true"]);
+        let options = CommitOptions {
+            sign: SignMode::NoSign,
+            ..CommitOptions::default()
+        };
+        create_commit(&repo, "test commit", &options).expect("unsigned commit should succeed");
+    }
+
     #[cfg(unix)]
     #[test]
     fn test_create_commit_returns_pre_commit_output() {
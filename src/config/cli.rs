@@ -1,13 +1,25 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::git::DiffSource;
+use crate::output::OutputFormat;
 
 /// A CLI tool that generates commit messages using AI
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Only output the generated commit message, without formatting
     #[arg(short, long)]
     pub message_only: bool,
 
+    /// Output format: `text` for the terminal, or `json` for a single machine-readable
+    /// document (message, template, stats, usage, cost) for editor/CI integrations
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
     /// Hide the diff statistics for staged changes
     #[arg(long, default_value_t = false)]
     pub no_diff_stats: bool,
@@ -16,14 +28,32 @@ pub struct Args {
     #[arg(long, default_value_t = false)]
     pub show_raw_diff: bool,
 
+    /// Print the fully assembled system and user prompts without calling the model
+    #[arg(long, default_value_t = false)]
+    pub show_prompt: bool,
+
     /// Do not scrub likely secrets (API keys, tokens, private keys) from the diff
     #[arg(long, default_value_t = false)]
     pub no_redact: bool,
 
+    /// List each redacted secret (file, line, rule) and ask before the diff is sent
+    #[arg(long, default_value_t = false)]
+    pub review_redactions: bool,
+
+    /// Exit with an error, sending and committing nothing, if the changes
+    /// contain a likely secret (works with --no-redact too)
+    #[arg(long, default_value_t = false)]
+    pub fail_on_secrets: bool,
+
     /// Number of context lines to show in the git diff
     #[arg(long, default_value_t = 20)]
     pub context_lines: u32,
 
+    /// Show the whole enclosing function, class or impl around each change
+    /// (Rust, TS/JS, Python, Go, Java; other files keep --context-lines)
+    #[arg(short = 'W', long)]
+    pub function_context: bool,
+
     /// Use a specific AI model (defaults to the selected provider's default model)
     #[arg(long)]
     pub model: Option<String>,
@@ -52,6 +82,20 @@ pub struct Args {
     #[arg(long, default_value_t = 5000)]
     pub max_file_lines: usize,
 
+    /// Similarity percentage for detecting renamed and copied files (0 disables)
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = crate::config::defaults::RENAME_THRESHOLD,
+        value_parser = clap::value_parser!(u16).range(0..=100)
+    )]
+    pub rename_threshold: u16,
+
+    /// Token budget for the diff; the most important files and hunks are kept
+    /// when it's exceeded (defaults to 75% of the model's input window, 0 disables)
+    #[arg(long, value_name = "TOKENS")]
+    pub max_diff_tokens: Option<usize>,
+
     /// Use a specific template for the commit message
     #[arg(long)]
     pub template: Option<String>,
@@ -108,10 +152,82 @@ pub struct Args {
     #[arg(long, short = 'y')]
     pub yes: bool,
 
+    /// Describe a unified diff from this file (e.g. an emailed patch) instead of
+    /// the staged changes; works outside a repository and never commits
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["stdin_diff", "yes", "all"])]
+    pub diff_file: Option<PathBuf>,
+
+    /// Like --diff-file, but read the diff from stdin (`git diff A B | cmt --stdin-diff`)
+    #[arg(long, conflicts_with_all = ["yes", "all"])]
+    pub stdin_diff: bool,
+
+    /// Describe unstaged working-tree changes (working tree vs index) instead
+    /// of the staged changes; never commits
+    #[arg(long, conflicts_with_all = ["from", "diff_file", "stdin_diff", "yes", "all"])]
+    pub unstaged: bool,
+
+    /// Describe the changes since this revision (vs the working tree, or vs
+    /// --to); never commits
+    #[arg(long, value_name = "REV", conflicts_with_all = ["diff_file", "stdin_diff", "yes", "all"])]
+    pub from: Option<String>,
+
+    /// End of the --from range: any revision or tree-ish
+    #[arg(long, value_name = "REV", requires = "from")]
+    pub to: Option<String>,
+
+    /// Commit as `fixup! <subject>` for a later autosquash: with no value,
+    /// suggest the unpushed commit that last touched the staged lines
+    #[arg(
+        long,
+        value_name = "REV",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with_all = ["diff_file", "stdin_diff", "unstaged", "from"]
+    )]
+    pub fixup: Option<Option<String>>,
+
+    /// Percentage of the changed lines the suggested --fixup target must have
+    /// last touched; weaker matches get a new message instead
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = crate::config::defaults::FIXUP_THRESHOLD,
+        value_parser = clap::value_parser!(u16).range(1..=100)
+    )]
+    pub fixup_threshold: u16,
+
     /// Skip pre-commit and commit-msg hooks
     #[arg(long, short = 'n')]
     pub no_verify: bool,
 
+    /// GPG/SSH-sign the commit, optionally with a specific key id (also
+    /// -S[KEYID], as in git)
+    #[arg(
+        long,
+        value_name = "KEYID",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    pub gpg_sign: Option<String>,
+
+    /// Do not sign the commit, overriding commit.gpgSign and the config file
+    #[arg(long, conflicts_with = "gpg_sign")]
+    pub no_gpg_sign: bool,
+
+    /// Add a Signed-off-by trailer from git config user.name/email
+    #[arg(long, short = 's')]
+    pub signoff: bool,
+
+    /// Add a git trailer, e.g. "Reviewed-by: Name <email>" (repeatable)
+    #[arg(long, value_name = "KEY: VALUE")]
+    pub trailer: Vec<String>,
+
+    /// Add a Co-authored-by trailer; matches a recent author's name or email,
+    /// or takes "Name <email>" verbatim (repeatable)
+    #[arg(long, value_name = "AUTHOR")]
+    pub co_author: Vec<String>,
+
     /// Reasoning depth for AI models (none=fastest, minimal, low, high)
     #[arg(long, default_value = "low", value_parser = ["none", "minimal", "low", "high"])]
     pub thinking: String,
@@ -121,9 +237,77 @@ pub struct Args {
     pub timeout: u64,
 }
 
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run a JSON-RPC server for editor plugins (one request per line, over stdio
+    /// or a Unix socket), keeping config, templates and pricing warm
+    Serve {
+        /// Listen on this Unix socket path instead of stdin/stdout
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Write one message summarizing a branch for a squash merge, from the
+    /// combined diff and commit messages since the merge base with BASE
+    Squash {
+        /// Branch or revision the feature branch will be merged into
+        base: String,
+        /// Also squash: soft-reset to the merge base and commit, crediting
+        /// every other author with a Co-authored-by trailer
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Inspect the ignore lists (global ignore file, `ignore` config key,
+    /// `.cmtignore` files), skip rules and `.gitattributes`
+    Ignore {
+        #[command(subcommand)]
+        command: IgnoreCommand,
+    },
+}
+
+/// Rewrite git's `-S` / `-SKEYID` (key attached, no `=`) to `--gpg-sign` /
+/// `--gpg-sign=KEYID`, which clap can't express for a short flag with an
+/// optional value. `-S=KEYID` is left alone so it is rejected, as git does.
+fn git_style_sign_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut options_done = false;
+    args.map(|arg| {
+        if options_done || arg == "--" {
+            options_done = true;
+            return arg;
+        }
+        match arg.strip_prefix("-S") {
+            Some("") => "--gpg-sign".to_string(),
+            Some(key) if !key.starts_with('=') => format!("--gpg-sign={}", key),
+            _ => arg,
+        }
+    })
+    .collect()
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum IgnoreCommand {
+    /// Explain which rule, if any, leaves PATH out of the prompt (exits 0
+    /// when it is ignored, 1 when it isn't, like `git check-ignore`)
+    Check {
+        /// File path, relative to the current directory
+        path: PathBuf,
+    },
+}
+
 impl Args {
     pub fn new_from(args: impl Iterator<Item = String>) -> Self {
-        Self::parse_from(args)
+        Self::parse_from(git_style_sign_args(args))
+    }
+
+    /// Which changes to describe, from `--unstaged` / `--from` / `--to`.
+    pub fn diff_source(&self) -> DiffSource {
+        match (&self.from, self.unstaged) {
+            (Some(from), _) => DiffSource::Revisions {
+                from: from.clone(),
+                to: self.to.clone(),
+            },
+            (None, true) => DiffSource::Unstaged,
+            (None, false) => DiffSource::Staged,
+        }
     }
 }
 
@@ -195,6 +379,239 @@ mod tests {
         assert!(!args.no_verify);
     }
 
+    #[test]
+    fn test_gpg_sign_options() {
+        let args = Args::new_from(["cmt", "-S"].iter().map(ToString::to_string));
+        assert_eq!(args.gpg_sign.as_deref(), Some(""));
+
+        let args = Args::new_from(["cmt", "-SABCD1234"].iter().map(ToString::to_string));
+        assert_eq!(args.gpg_sign.as_deref(), Some("ABCD1234"));
+
+        // git takes the key attached; `-S=KEY` and `-S KEY` are not key ids
+        let parse = |argv: &[&str]| {
+            Args::try_parse_from(git_style_sign_args(argv.iter().map(ToString::to_string)))
+        };
+        assert!(parse(&["cmt", "-S=ABCD1234"]).is_err());
+        let args = parse(&["cmt", "-S", "--hint", "x"]).unwrap();
+        assert_eq!(args.gpg_sign.as_deref(), Some(""));
+
+        let args = Args::new_from(
+            ["cmt", "--gpg-sign=ABCD1234"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(args.gpg_sign.as_deref(), Some("ABCD1234"));
+
+        let args = Args::new_from(["cmt", "--no-gpg-sign"].iter().map(ToString::to_string));
+        assert!(args.no_gpg_sign);
+        assert!(args.gpg_sign.is_none());
+
+        assert!(parse(&["cmt", "-S", "--no-gpg-sign"]).is_err());
+    }
+
+    #[test]
+    fn test_trailer_options() {
+        let args = Args::new_from(
+            [
+                "cmt",
+                "-s",
+                "--trailer",
+                "Reviewed-by: Ada <ada@example.com>",
+                "--trailer",
+                "Change-Id: I0123",
+                "--co-author",
+                "grace",
+            ]
+            .iter()
+            .map(ToString::to_string),
+        );
+        assert!(args.signoff);
+        assert_eq!(args.trailer.len(), 2);
+        assert_eq!(args.co_author, vec!["grace".to_string()]);
+
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert!(!args.signoff);
+        assert!(args.trailer.is_empty());
+    }
+
+    #[test]
+    fn test_serve_subcommand() {
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert!(args.command.is_none());
+
+        let args = Args::new_from(["cmt", "serve"].iter().map(ToString::to_string));
+        assert_eq!(args.command, Some(Command::Serve { socket: None }));
+
+        let args = Args::new_from(
+            [
+                "cmt",
+                "--provider",
+                "claude",
+                "serve",
+                "--socket",
+                "/tmp/cmt.sock",
+            ]
+            .iter()
+            .map(ToString::to_string),
+        );
+        assert_eq!(args.provider, "claude");
+        assert_eq!(
+            args.command,
+            Some(Command::Serve {
+                socket: Some(PathBuf::from("/tmp/cmt.sock"))
+            })
+        );
+    }
+
+    #[test]
+    fn test_external_diff_flags() {
+        let args = Args::new_from(
+            ["cmt", "--diff-file", "fix.patch"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(args.diff_file, Some(PathBuf::from("fix.patch")));
+        assert!(!args.stdin_diff);
+
+        let args = Args::new_from(["cmt", "--stdin-diff"].iter().map(ToString::to_string));
+        assert!(args.stdin_diff);
+
+        // An external diff can't be committed, and only one source is allowed.
+        assert!(Args::try_parse_from(["cmt", "--stdin-diff", "-y"]).is_err());
+        assert!(Args::try_parse_from(["cmt", "--diff-file", "a.patch", "--stdin-diff"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_source_flags() {
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert_eq!(args.diff_source(), DiffSource::Staged);
+
+        let args = Args::new_from(["cmt", "--unstaged"].iter().map(ToString::to_string));
+        assert_eq!(args.diff_source(), DiffSource::Unstaged);
+
+        let args = Args::new_from(
+            ["cmt", "--from", "v1.0", "--to", "HEAD~1"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(
+            args.diff_source(),
+            DiffSource::Revisions {
+                from: "v1.0".to_string(),
+                to: Some("HEAD~1".to_string()),
+            }
+        );
+
+        let args = Args::new_from(["cmt", "--from", "main"].iter().map(ToString::to_string));
+        assert_eq!(
+            args.diff_source(),
+            DiffSource::Revisions {
+                from: "main".to_string(),
+                to: None,
+            }
+        );
+
+        assert!(Args::try_parse_from(["cmt", "--to", "HEAD"]).is_err());
+        assert!(Args::try_parse_from(["cmt", "--unstaged", "--from", "main"]).is_err());
+        assert!(Args::try_parse_from(["cmt", "--unstaged", "-y"]).is_err());
+        assert!(Args::try_parse_from(["cmt", "--from", "main", "--stdin-diff"]).is_err());
+    }
+
+    #[test]
+    fn test_squash_subcommand() {
+        let args = Args::new_from(["cmt", "squash", "main"].iter().map(ToString::to_string));
+        assert_eq!(
+            args.command,
+            Some(Command::Squash {
+                base: "main".to_string(),
+                commit: false,
+            })
+        );
+
+        let args = Args::new_from(
+            ["cmt", "-y", "squash", "origin/main", "--commit"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert!(args.yes);
+        assert_eq!(
+            args.command,
+            Some(Command::Squash {
+                base: "origin/main".to_string(),
+                commit: true,
+            })
+        );
+
+        assert!(Args::try_parse_from(["cmt", "squash"]).is_err());
+    }
+
+    #[test]
+    fn test_secret_guard_flags() {
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert!(!args.review_redactions && !args.fail_on_secrets);
+
+        let args = Args::new_from(
+            ["cmt", "--review-redactions", "--fail-on-secrets"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert!(args.review_redactions && args.fail_on_secrets);
+    }
+
+    #[test]
+    fn test_ignore_check_subcommand() {
+        let args = Args::new_from(
+            ["cmt", "ignore", "check", "src/secrets.pem"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(
+            args.command,
+            Some(Command::Ignore {
+                command: IgnoreCommand::Check {
+                    path: PathBuf::from("src/secrets.pem"),
+                },
+            })
+        );
+
+        assert!(Args::try_parse_from(["cmt", "ignore", "check"]).is_err());
+    }
+
+    #[test]
+    fn test_fixup_flag() {
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert_eq!(args.fixup, None);
+
+        let args = Args::new_from(["cmt", "--fixup"].iter().map(ToString::to_string));
+        assert_eq!(args.fixup, Some(None));
+
+        let args = Args::new_from(["cmt", "--fixup=HEAD~2"].iter().map(ToString::to_string));
+        assert_eq!(args.fixup, Some(Some("HEAD~2".to_string())));
+
+        assert!(Args::try_parse_from(["cmt", "--fixup", "--unstaged"]).is_err());
+
+        assert_eq!(args.fixup_threshold, 50);
+        let args = Args::new_from(
+            ["cmt", "--fixup", "--fixup-threshold", "80"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(args.fixup_threshold, 80);
+        assert!(Args::try_parse_from(["cmt", "--fixup-threshold", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_output_flag() {
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert_eq!(args.output, OutputFormat::Text);
+
+        let args = Args::new_from(["cmt", "--output", "json"].iter().map(ToString::to_string));
+        assert_eq!(args.output, OutputFormat::Json);
+
+        let result = Args::try_parse_from(["cmt", "--output", "yaml"]);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_message_only_flag() {
         let args = Args::new_from(["cmt", "--message-only"].iter().map(ToString::to_string));
@@ -298,6 +715,12 @@ mod tests {
         assert!(args.show_raw_diff);
     }
 
+    #[test]
+    fn test_show_prompt_flag() {
+        let args = Args::new_from(["cmt", "--show-prompt"].iter().map(ToString::to_string));
+        assert!(args.show_prompt);
+    }
+
     #[test]
     fn test_context_lines_option() {
         let args = Args::new_from(
@@ -308,6 +731,14 @@ mod tests {
         assert_eq!(args.context_lines, 10);
     }
 
+    #[test]
+    fn test_function_context_flag() {
+        let args = Args::new_from(["cmt", "-W"].iter().map(ToString::to_string));
+        assert!(args.function_context);
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert!(!args.function_context);
+    }
+
     #[test]
     fn test_max_file_lines_option() {
         let args = Args::new_from(
@@ -322,6 +753,35 @@ mod tests {
         assert_eq!(args.max_file_lines, 5000);
     }
 
+    #[test]
+    fn test_rename_threshold_option() {
+        let args = Args::new_from(
+            ["cmt", "--rename-threshold", "80"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(args.rename_threshold, 80);
+
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert_eq!(args.rename_threshold, 50);
+
+        let result = Args::try_parse_from(["cmt", "--rename-threshold", "101"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_max_diff_tokens_option() {
+        let args = Args::new_from(
+            ["cmt", "--max-diff-tokens", "30000"]
+                .iter()
+                .map(ToString::to_string),
+        );
+        assert_eq!(args.max_diff_tokens, Some(30000));
+
+        let args = Args::new_from(["cmt"].iter().map(ToString::to_string));
+        assert_eq!(args.max_diff_tokens, None);
+    }
+
     #[test]
     fn test_list_templates_flag() {
         let args = Args::new_from(["cmt", "--list-templates"].iter().map(ToString::to_string));
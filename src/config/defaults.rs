@@ -8,6 +8,9 @@ pub const CONTEXT_LINES: u32 = 20; // Full function context - Gemini Flash suppo
 pub const MAX_LINES_PER_FILE: usize = 2000; // Allow large files - we have token budget
 pub const MAX_LINE_WIDTH: usize = 500; // Allow wider lines for better context
 pub const MAX_FILE_LINES: usize = 5000; // Threshold for prompting to add to .cmtignore
+pub const RENAME_THRESHOLD: u16 = 50; // Similarity % for rename/copy detection, like git's -M
+pub const FUNCTION_CONTEXT: bool = false; // Widen hunks to the enclosing function/type
+pub const FIXUP_THRESHOLD: u16 = 50; // % of changed lines a --fixup suggestion must have last touched
 
 // Cmtignore defaults
 pub const CMTIGNORE_FILENAME: &str = ".cmtignore";
@@ -20,15 +23,18 @@ pub const REDACT: bool = true; // Scrub likely secrets from the diff before send
 
 // Git defaults
 pub const INCLUDE_RECENT_COMMITS: bool = true;
+pub const SIGNOFF: bool = false; // Append Signed-off-by from git config user.name/email
 pub const RECENT_COMMITS_COUNT: usize = 10; // More history for better context
 
 // File paths
 pub const DEFAULT_CONFIG_FILENAME: &str = ".cmt.toml";
 pub const GLOBAL_CONFIG_DIRNAME: &str = ".config/cmt";
 pub const GLOBAL_CONFIG_FILENAME: &str = "config.toml";
+pub const GLOBAL_IGNORE_FILENAME: &str = "ignore"; // .cmtignore syntax, applied to every repository
 
 // Template defaults
 pub const DEFAULT_TEMPLATE: &str = "conventional";
+pub const DEFAULT_ISSUE_FOOTER: &str = "Fixes: {id}"; // `{id}` is replaced per reference
 
 // Available providers
 pub const AVAILABLE_PROVIDERS: &[&str] = &["claude", "openai", "gemini"];
@@ -57,6 +63,11 @@ context_lines = {}
 max_lines_per_file = {}
 max_line_width = {}
 max_file_lines = {}
+rename_threshold = {}  # Similarity % to detect renames/copies (0 disables)
+function_context = {}  # Widen hunks to the enclosing function/class/impl (Rust, TS/JS, Python, Go, Java)
+# max_diff_tokens = 100000  # Diff token budget (default: 75% of the model's input window; 0 disables)
+# ignore = ["*.pem", "secrets/"]  # .cmtignore patterns; a repo's .cmtignore can't re-include these
+fixup_threshold = {}  # % of the changed lines a --fixup suggestion must have last touched
 
 # AI provider options
 provider = "{}"  # Options: {}
@@ -68,12 +79,69 @@ timeout = {}  # Per-request timeout in seconds for the LLM call
 # Git options
 include_recent_commits = {}
 recent_commits_count = {}
+signoff = {}  # Append a Signed-off-by trailer from git config user.name/email
+# trailers = ["Reviewed-by: Ada Lovelace <ada@example.com>"]  # Static trailers
+# co_authors = ["grace"]  # Co-authored-by, matched against recent commit authors
+# gpg_sign = true  # Sign commits (false never signs; unset defers to git config)
+# signing_key = "ABCD1234"  # Sign with this key id (implies gpg_sign unless it is false)
 
 # Template options
 # template = "{}"  # Uncomment to use a specific template
 
+# Issue references: regexes that pull ticket IDs from the branch name (first
+# capture group if present, else the whole match). When set, the IDs always
+# fill the issues footer instead of whatever the model guesses.
+# issue_patterns = ["[A-Z]+-\\d+", "issue-(\\d+)"]
+# issue_footer = "Refs: #{{id}}"  # Default: "{}"
+
 # You can add a default hint that will be used for all commits
 # hint = "Focus on the technical details"
+
+# Prompt overrides (Handlebars: {{{{changes}}}}, {{{{branch}}}}, {{{{recent_commits}}}}, {{{{readme}}}}, {{{{hint}}}}).
+# The same can be placed in .cmt/prompts/{{system,user}}.txt (replace) or
+# .cmt/prompts/{{system,user}}.append.txt (append); keys here take precedence.
+# system_prompt_append = "Always mention the Jira ticket. Never mention tests."
+# user_prompt_append = "Use our domain vocabulary: 'ledger', not 'account book'."
+
+# Skip rules: files left out of the diff automatically. Built-in rules are
+# lockfile, generated-asset, image, build-output and binary (content sniffing).
+# [skip]
+# builtin = true  # false turns off every built-in rule
+# disable = ["image"]  # Turn off built-in rules by name
+#
+# [[skip.rules]]
+# name = "protobuf"
+# globs = ["**/*.pb.go"]  # A glob without '/' matches the file name at any depth
+# extensions = ["pb"]  # Compound suffixes like "min.js" work too
+# min_bytes = 100000  # Only files at least this large (alone: any file)
+# binary = false  # Only files whose content looks binary
+
+# Secret redaction: built-in rules cover cloud, VCS, payment and AI provider
+# tokens, private keys, URL passwords and key = value credentials; quoted
+# strings that look random are caught by their Shannon entropy.
+# [secrets]
+# rules_file = ".gitleaks.toml"  # Extra gitleaks-format rules and allowlists (repo-relative)
+# entropy = true  # false redacts known formats only
+# base64_entropy = 4.5  # Bits per character for base64-like strings
+# hex_entropy = 3.0  # Bits per character for hex strings
+# entropy_min_length = 20  # Shorter strings are never checked
+# fail_on_secrets = true  # Abort (nothing sent, nothing committed) when a secret is found
+# review_redactions = true  # List redactions and confirm before sending
+
+# Personal data: each enabled category is replaced with a stable placeholder
+# ([EMAIL_1], [PHONE_1], [IP_1], [CARD_1]), the same one for the same value.
+# [pii]
+# emails = true
+# phones = true
+# ip_addresses = true
+# credit_cards = true  # Luhn-valid numbers with a card issuer prefix
+
+# Reversible placeholders: matches are sent as <<LABEL_n>> tokens (here
+# <<HOST_1>>, <<CUSTOMER_1>>) and put back into the generated message locally.
+# [placeholders.host]
+# patterns = ['[a-z0-9-]+\.corp\.internal']  # Regular expressions
+# [placeholders.customer]
+# values = ["Acme Corp", "Globex"]  # Whole words, any case
 "#,
         MESSAGE_ONLY,
         NO_DIFF_STATS,
@@ -83,6 +151,9 @@ recent_commits_count = {}
         MAX_LINES_PER_FILE,
         MAX_LINE_WIDTH,
         MAX_FILE_LINES,
+        RENAME_THRESHOLD,
+        FUNCTION_CONTEXT,
+        FIXUP_THRESHOLD,
         DEFAULT_PROVIDER,
         AVAILABLE_PROVIDERS.join(", "),
         DEFAULT_CLAUDE_MODEL,
@@ -90,7 +161,9 @@ recent_commits_count = {}
         TIMEOUT_SECS,
         INCLUDE_RECENT_COMMITS,
         RECENT_COMMITS_COUNT,
+        SIGNOFF,
         DEFAULT_TEMPLATE,
+        DEFAULT_ISSUE_FOOTER,
     )
 }
 
@@ -114,7 +187,7 @@ pub fn conventional_template() -> String {
 {{/if}}
 
 {{#if issues}}
-Fixes: {{{issues}}}
+{{{issues_footer}}}
 {{/if}}
 
 {{#if breaking}}
@@ -132,7 +205,7 @@ pub fn detailed_template() -> String {
 {{/if}}
 
 {{#if issues}}
-Fixes: {{{issues}}}
+{{{issues_footer}}}
 {{/if}}
 
 {{#if breaking}}
@@ -48,6 +48,12 @@ pub fn global_config_file() -> Option<PathBuf> {
     global_config_dir().map(|dir| dir.join(defaults::GLOBAL_CONFIG_FILENAME))
 }
 
+/// Get the global ignore file path (`.cmtignore` syntax, applied to every
+/// repository)
+pub fn global_ignore_file() -> Option<PathBuf> {
+    global_config_dir().map(|dir| dir.join(defaults::GLOBAL_IGNORE_FILENAME))
+}
+
 /// Create the global configuration directory and file
 pub fn create_global_config() -> Result<PathBuf, ConfigError> {
     let global_dir = global_config_dir().ok_or_else(|| {
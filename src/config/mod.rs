@@ -2,6 +2,7 @@ pub mod cli;
 pub mod defaults;
 pub mod file;
 
+use std::collections::BTreeMap;
 use std::env;
 use std::error::Error;
 use std::fmt;
@@ -10,6 +11,9 @@ use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::redact::{PiiConfig, PlaceholderRule, SecretsConfig};
+use crate::skip::SkipConfig;
+
 /// Configuration error type
 #[derive(Debug)]
 pub enum ConfigError {
@@ -49,10 +53,29 @@ pub struct Config {
     pub no_diff_stats: bool,
     pub show_raw_diff: bool,
     pub redact: bool,
+    /// Secret detection: extra rules file and entropy thresholds (`[secrets]`)
+    pub secrets: SecretsConfig,
+    /// Personal data categories to pseudonymize (`[pii]`)
+    pub pii: PiiConfig,
+    /// Identifiers sent as reversible `<<LABEL_n>>` tokens, by label
+    /// (`[placeholders.<label>]`)
+    pub placeholders: BTreeMap<String, PlaceholderRule>,
     pub context_lines: u32,
     pub max_lines_per_file: usize,
     pub max_line_width: usize,
     pub max_file_lines: usize,
+    pub rename_threshold: u16,
+    pub function_context: bool,
+    /// Estimated tokens the diff may use; unset derives it from the model's
+    /// input window, 0 disables the budget
+    pub max_diff_tokens: Option<usize>,
+    /// Rules for files left out automatically (`[skip]` table)
+    pub skip: SkipConfig,
+    /// `.cmtignore` patterns that apply on top of the repository's files
+    pub ignore: Vec<String>,
+    /// Percentage of the staged changed lines a suggested `--fixup` target
+    /// must have last touched
+    pub fixup_threshold: u16,
 
     // AI provider options
     pub provider: String,
@@ -64,12 +87,31 @@ pub struct Config {
     // Git options
     pub include_recent_commits: bool,
     pub recent_commits_count: usize,
+    /// Commit signing: unset defers to git config, true signs, false never signs
+    pub gpg_sign: Option<bool>,
+    pub signing_key: Option<String>,
 
     // Template options
     pub template: Option<String>,
+    pub issue_patterns: Vec<String>,
+    /// Footer line per issue reference, `{id}` replaced (default
+    /// `Fixes: {id}`). `None` when no source set it, so any layer can set
+    /// it back to the default.
+    pub issue_footer: Option<String>,
+
+    // Trailers (appended after rendering; lists accumulate across sources)
+    pub signoff: bool,
+    pub trailers: Vec<String>,
+    pub co_authors: Vec<String>,
 
     // Additional context
     pub hint: Option<String>,
+
+    // Prompt overrides (also loadable from .cmt/prompts/*.txt)
+    pub system_prompt: Option<String>,
+    pub system_prompt_append: Option<String>,
+    pub user_prompt: Option<String>,
+    pub user_prompt_append: Option<String>,
 }
 
 impl Default for Config {
@@ -79,10 +121,19 @@ impl Default for Config {
             no_diff_stats: defaults::NO_DIFF_STATS,
             show_raw_diff: defaults::SHOW_RAW_DIFF,
             redact: defaults::REDACT,
+            secrets: SecretsConfig::default(),
+            pii: PiiConfig::default(),
+            placeholders: BTreeMap::new(),
             context_lines: defaults::CONTEXT_LINES,
             max_lines_per_file: defaults::MAX_LINES_PER_FILE,
             max_line_width: defaults::MAX_LINE_WIDTH,
             max_file_lines: defaults::MAX_FILE_LINES,
+            rename_threshold: defaults::RENAME_THRESHOLD,
+            function_context: defaults::FUNCTION_CONTEXT,
+            max_diff_tokens: None,
+            skip: SkipConfig::default(),
+            ignore: Vec::new(),
+            fixup_threshold: defaults::FIXUP_THRESHOLD,
             provider: defaults::DEFAULT_PROVIDER.to_string(),
             model: None,
             temperature: None,
@@ -90,8 +141,19 @@ impl Default for Config {
             timeout_secs: defaults::TIMEOUT_SECS,
             include_recent_commits: defaults::INCLUDE_RECENT_COMMITS,
             recent_commits_count: defaults::RECENT_COMMITS_COUNT,
+            gpg_sign: None,
+            signing_key: None,
             template: None,
+            issue_patterns: Vec::new(),
+            issue_footer: None,
+            signoff: defaults::SIGNOFF,
+            trailers: Vec::new(),
+            co_authors: Vec::new(),
             hint: None,
+            system_prompt: None,
+            system_prompt_append: None,
+            user_prompt: None,
+            user_prompt_append: None,
         }
     }
 }
@@ -160,6 +222,38 @@ impl Config {
         if other.redact != defaults::REDACT {
             self.redact = other.redact;
         }
+        let default_secrets = SecretsConfig::default();
+        if other.secrets.rules_file.is_some() {
+            self.secrets.rules_file = other.secrets.rules_file.clone();
+        }
+        if other.secrets.entropy != default_secrets.entropy {
+            self.secrets.entropy = other.secrets.entropy;
+        }
+        if other.secrets.base64_entropy != default_secrets.base64_entropy {
+            self.secrets.base64_entropy = other.secrets.base64_entropy;
+        }
+        if other.secrets.hex_entropy != default_secrets.hex_entropy {
+            self.secrets.hex_entropy = other.secrets.hex_entropy;
+        }
+        if other.secrets.entropy_min_length != default_secrets.entropy_min_length {
+            self.secrets.entropy_min_length = other.secrets.entropy_min_length;
+        }
+        if other.secrets.fail_on_secrets != default_secrets.fail_on_secrets {
+            self.secrets.fail_on_secrets = other.secrets.fail_on_secrets;
+        }
+        if other.secrets.review_redactions != default_secrets.review_redactions {
+            self.secrets.review_redactions = other.secrets.review_redactions;
+        }
+        // A category set (on or off) in a higher layer wins
+        self.pii.emails = other.pii.emails.or(self.pii.emails);
+        self.pii.phones = other.pii.phones.or(self.pii.phones);
+        self.pii.ip_addresses = other.pii.ip_addresses.or(self.pii.ip_addresses);
+        self.pii.credit_cards = other.pii.credit_cards.or(self.pii.credit_cards);
+        for (label, rule) in &other.placeholders {
+            let merged = self.placeholders.entry(label.clone()).or_default();
+            merged.values.extend(rule.values.iter().cloned());
+            merged.patterns.extend(rule.patterns.iter().cloned());
+        }
         if other.context_lines != defaults::CONTEXT_LINES {
             self.context_lines = other.context_lines;
         }
@@ -172,6 +266,24 @@ impl Config {
         if other.max_file_lines != defaults::MAX_FILE_LINES {
             self.max_file_lines = other.max_file_lines;
         }
+        if other.rename_threshold != defaults::RENAME_THRESHOLD {
+            self.rename_threshold = other.rename_threshold;
+        }
+        if other.function_context != defaults::FUNCTION_CONTEXT {
+            self.function_context = other.function_context;
+        }
+        if other.fixup_threshold != defaults::FIXUP_THRESHOLD {
+            self.fixup_threshold = other.fixup_threshold;
+        }
+        if other.max_diff_tokens.is_some() {
+            self.max_diff_tokens = other.max_diff_tokens;
+        }
+        if other.skip.builtin != SkipConfig::default().builtin {
+            self.skip.builtin = other.skip.builtin;
+        }
+        self.skip.disable.extend(other.skip.disable.iter().cloned());
+        self.skip.rules.extend(other.skip.rules.iter().cloned());
+        self.ignore.extend(other.ignore.iter().cloned());
         if other.provider != defaults::DEFAULT_PROVIDER {
             self.provider = other.provider.clone();
         }
@@ -193,12 +305,41 @@ impl Config {
         if other.recent_commits_count != defaults::RECENT_COMMITS_COUNT {
             self.recent_commits_count = other.recent_commits_count;
         }
+        if other.gpg_sign.is_some() {
+            self.gpg_sign = other.gpg_sign;
+        }
+        if other.signing_key.is_some() {
+            self.signing_key = other.signing_key.clone();
+        }
         if other.template.is_some() {
             self.template = other.template.clone();
         }
+        if !other.issue_patterns.is_empty() {
+            self.issue_patterns = other.issue_patterns.clone();
+        }
+        if other.issue_footer.is_some() {
+            self.issue_footer = other.issue_footer.clone();
+        }
+        if other.signoff != defaults::SIGNOFF {
+            self.signoff = other.signoff;
+        }
+        self.trailers.extend(other.trailers.iter().cloned());
+        self.co_authors.extend(other.co_authors.iter().cloned());
         if other.hint.is_some() {
             self.hint = other.hint.clone();
         }
+        if other.system_prompt.is_some() {
+            self.system_prompt = other.system_prompt.clone();
+        }
+        if other.system_prompt_append.is_some() {
+            self.system_prompt_append = other.system_prompt_append.clone();
+        }
+        if other.user_prompt.is_some() {
+            self.user_prompt = other.user_prompt.clone();
+        }
+        if other.user_prompt_append.is_some() {
+            self.user_prompt_append = other.user_prompt_append.clone();
+        }
     }
 
     /// Load configuration from CLI args
@@ -208,10 +349,22 @@ impl Config {
             no_diff_stats: args.no_diff_stats,
             show_raw_diff: args.show_raw_diff,
             redact: !args.no_redact,
+            secrets: SecretsConfig {
+                fail_on_secrets: args.fail_on_secrets,
+                review_redactions: args.review_redactions,
+                ..SecretsConfig::default()
+            },
+            pii: PiiConfig::default(),
+            placeholders: BTreeMap::new(),
             context_lines: args.context_lines,
             max_lines_per_file: args.max_lines_per_file,
             max_line_width: args.max_line_width,
             max_file_lines: args.max_file_lines,
+            rename_threshold: args.rename_threshold,
+            function_context: args.function_context,
+            max_diff_tokens: args.max_diff_tokens,
+            fixup_threshold: args.fixup_threshold,
+            skip: SkipConfig::default(),
             provider: args.provider.clone(),
             model: args.model.clone(),
             temperature: args.temperature,
@@ -219,8 +372,18 @@ impl Config {
             timeout_secs: args.timeout,
             include_recent_commits: !args.no_recent_commits,
             recent_commits_count: args.recent_commits_count,
+            gpg_sign: if args.no_gpg_sign {
+                Some(false)
+            } else {
+                args.gpg_sign.as_ref().map(|_| true)
+            },
+            signing_key: args.gpg_sign.clone().filter(|key| !key.is_empty()),
             template: args.template.clone(),
             hint: args.hint.clone(),
+            signoff: args.signoff,
+            trailers: args.trailer.clone(),
+            co_authors: args.co_author.clone(),
+            ..Self::default()
         }
     }
 
@@ -238,6 +401,12 @@ impl Config {
             }
         }
 
+        // Prompt files belong to the project layer: over the global config,
+        // under `.cmt.toml`
+        if let Some(repo_root) = Self::find_repo_root() {
+            config.merge_prompt_files(&repo_root);
+        }
+
         // Try to load project config
         if let Some(project_config_path) = Self::find_project_config() {
             if let Ok(project_config) = Self::from_file(&project_config_path) {
@@ -248,6 +417,20 @@ impl Config {
         Ok(config)
     }
 
+    /// Layer the prompt files in `<repo_root>/.cmt/prompts/` over the config
+    /// loaded so far.
+    fn merge_prompt_files(&mut self, repo_root: &Path) {
+        let mut files = Self::default();
+        crate::prompts::load_project_prompts(&mut files, repo_root);
+        self.merge(&files);
+    }
+
+    /// The work tree of the repository containing the current directory
+    fn find_repo_root() -> Option<PathBuf> {
+        let repo = git2::Repository::discover(env::current_dir().ok()?).ok()?;
+        repo.workdir().map(Path::to_path_buf)
+    }
+
     /// Get the global config path
     fn global_config_path() -> Option<PathBuf> {
         if let Ok(home) = env::var("HOME") {
@@ -344,6 +527,201 @@ mod tests {
         assert_eq!(cfg.thinking, defaults::DEFAULT_THINKING);
     }
 
+    #[test]
+    fn test_signing_options_from_args_and_file() {
+        let cfg = Config::from_args(&args_from(&["cmt", "-SABCD1234"]));
+        assert_eq!(cfg.gpg_sign, Some(true));
+        assert_eq!(cfg.signing_key.as_deref(), Some("ABCD1234"));
+
+        // A file that enables signing is overridden by --no-gpg-sign...
+        let mut merged = Config::default();
+        merged.merge(&Config {
+            gpg_sign: Some(true),
+            ..Config::default()
+        });
+        merged.merge(&Config::from_args(&args_from(&["cmt", "--no-gpg-sign"])));
+        assert_eq!(merged.gpg_sign, Some(false));
+
+        // ...but survives a CLI run that doesn't mention signing.
+        let mut merged = Config::default();
+        merged.merge(&Config {
+            gpg_sign: Some(true),
+            ..Config::default()
+        });
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert_eq!(merged.gpg_sign, Some(true));
+    }
+
+    #[test]
+    fn test_example_config_parses_with_commented_options() {
+        let example = defaults::example_config();
+        let cfg: Config = toml::from_str(&example).expect("example config must parse");
+        assert_eq!(cfg.issue_footer, None);
+
+        // Uncommenting the documented issue options must also parse.
+        let uncommented = example
+            .replace("# issue_patterns", "issue_patterns")
+            .replace("# issue_footer", "issue_footer");
+        let cfg: Config = toml::from_str(&uncommented).expect("issue options must parse");
+        assert_eq!(cfg.issue_patterns, vec![r"[A-Z]+-\d+", r"issue-(\d+)"]);
+        assert_eq!(cfg.issue_footer.as_deref(), Some("Refs: #{id}"));
+
+        // A project config can set the footer back to the default
+        let mut merged = Config::default();
+        merged.merge(&cfg);
+        merged.merge(&toml::from_str("issue_footer = \"Fixes: {id}\"\n").unwrap());
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert_eq!(
+            merged.issue_footer.as_deref(),
+            Some(defaults::DEFAULT_ISSUE_FOOTER)
+        );
+    }
+
+    #[test]
+    fn test_skip_rules_from_file_accumulate() {
+        let file: Config = toml::from_str(
+            "[skip]\n\
+             disable = [\"image\"]\n\
+             \n\
+             [[skip.rules]]\n\
+             name = \"protobuf\"\n\
+             globs = [\"**/*.pb.go\"]\n",
+        )
+        .expect("skip table must parse");
+        assert!(file.skip.builtin);
+        assert_eq!(file.skip.rules[0].name, "protobuf");
+
+        let uncommented = defaults::example_config()
+            .replace("# [skip]", "[skip]")
+            .replace("# builtin", "builtin")
+            .replace("# disable", "disable")
+            .replace("# [[skip.rules]]", "[[skip.rules]]")
+            .replace("# name", "name")
+            .replace("# globs", "globs")
+            .replace("# extensions", "extensions")
+            .replace("# min_bytes", "min_bytes")
+            .replace("# binary", "binary");
+        let example: Config = toml::from_str(&uncommented).expect("skip options must parse");
+        assert_eq!(example.skip.rules[0].min_bytes, Some(100000));
+
+        let mut merged = Config::default();
+        merged.merge(&Config {
+            skip: SkipConfig {
+                disable: vec!["lockfile".to_string()],
+                ..SkipConfig::default()
+            },
+            ..Config::default()
+        });
+        merged.merge(&file);
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert!(merged.skip.builtin);
+        assert_eq!(merged.skip.disable, ["lockfile", "image"]);
+        assert_eq!(merged.skip.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_ignore_lists_accumulate() {
+        let uncommented = defaults::example_config().replace("# ignore =", "ignore =");
+        let example: Config = toml::from_str(&uncommented).expect("ignore option must parse");
+        assert_eq!(example.ignore, ["*.pem", "secrets/"]);
+
+        let mut merged = Config::default();
+        merged.merge(&example);
+        merged.merge(&Config {
+            ignore: vec!["*.dump".to_string()],
+            ..Config::default()
+        });
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert_eq!(merged.ignore, ["*.pem", "secrets/", "*.dump"]);
+    }
+
+    #[test]
+    fn test_secrets_table() {
+        let uncommented = defaults::example_config()
+            .replace("# [secrets]", "[secrets]")
+            .replace("# rules_file", "rules_file")
+            .replace("# entropy =", "entropy =")
+            .replace("# base64_entropy", "base64_entropy")
+            .replace("# hex_entropy", "hex_entropy")
+            .replace("# entropy_min_length", "entropy_min_length");
+        let example: Config = toml::from_str(&uncommented).expect("secrets options must parse");
+        assert_eq!(
+            example.secrets,
+            SecretsConfig {
+                rules_file: Some(".gitleaks.toml".to_string()),
+                ..SecretsConfig::default()
+            }
+        );
+
+        let mut merged = Config::default();
+        merged.merge(&example);
+        merged.merge(&Config {
+            secrets: SecretsConfig {
+                entropy: false,
+                ..SecretsConfig::default()
+            },
+            ..Config::default()
+        });
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert_eq!(merged.secrets.rules_file.as_deref(), Some(".gitleaks.toml"));
+        assert!(!merged.secrets.entropy);
+    }
+
+    #[test]
+    fn test_pii_table() {
+        let uncommented = defaults::example_config()
+            .replace("# [pii]", "[pii]")
+            .replace("# emails", "emails")
+            .replace("# phones", "phones")
+            .replace("# ip_addresses", "ip_addresses")
+            .replace("# credit_cards", "credit_cards");
+        let example: Config = toml::from_str(&uncommented).expect("pii options must parse");
+        assert!(example.pii.emails == Some(true) && example.pii.phones == Some(true));
+        assert!(example.pii.ip_addresses == Some(true) && example.pii.credit_cards == Some(true));
+
+        let mut merged = Config::default();
+        merged.merge(&toml::from_str("[pii]\nemails = true\n").unwrap());
+        merged.merge(&toml::from_str("[pii]\nphones = true\n").unwrap());
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert_eq!(
+            merged.pii,
+            PiiConfig {
+                emails: Some(true),
+                phones: Some(true),
+                ..PiiConfig::default()
+            }
+        );
+
+        // A project can turn off a category enabled globally.
+        merged.merge(&toml::from_str("[pii]\nemails = false\n").unwrap());
+        assert_eq!(merged.pii.emails, Some(false));
+        assert_eq!(merged.pii.phones, Some(true));
+        assert!(merged.pii.is_enabled());
+    }
+
+    #[test]
+    fn test_placeholder_tables_accumulate() {
+        let uncommented = defaults::example_config()
+            .replace("# [placeholders", "[placeholders")
+            .replace("# patterns", "patterns")
+            .replace("# values", "values");
+        let example: Config = toml::from_str(&uncommented).expect("placeholder options must parse");
+        assert_eq!(
+            example.placeholders["host"].patterns,
+            [r"[a-z0-9-]+\.corp\.internal"]
+        );
+
+        let mut merged = Config::default();
+        merged.merge(&example);
+        merged.merge(&toml::from_str("[placeholders.customer]\nvalues = [\"Initech\"]\n").unwrap());
+        merged.merge(&Config::from_args(&args_from(&["cmt"])));
+        assert_eq!(
+            merged.placeholders["customer"].values,
+            ["Acme Corp", "Globex", "Initech"]
+        );
+        assert_eq!(merged.placeholders.len(), 2);
+    }
+
     #[test]
     fn test_cli_overrides_file() {
         let mut merged = Config::default();
@@ -358,4 +736,26 @@ mod tests {
         ])));
         assert_eq!(merged.provider, "openai");
     }
+
+    #[test]
+    fn test_project_prompt_files_override_global_prompts() {
+        let root = tempfile::TempDir::new().unwrap();
+        let prompts = root.path().join(".cmt").join("prompts");
+        fs::create_dir_all(&prompts).unwrap();
+        fs::write(prompts.join("system.txt"), "project system").unwrap();
+        fs::write(prompts.join("user.txt"), "project user").unwrap();
+
+        // defaults < global < prompt files < .cmt.toml
+        let mut config = Config::default();
+        config.merge(&Config {
+            system_prompt: Some("global system".to_string()),
+            user_prompt: Some("global user".to_string()),
+            ..Config::default()
+        });
+        config.merge_prompt_files(root.path());
+        config.merge(&toml::from_str("user_prompt = \"project toml\"\n").unwrap());
+
+        assert_eq!(config.system_prompt.as_deref(), Some("project system"));
+        assert_eq!(config.user_prompt.as_deref(), Some("project toml"));
+    }
 }
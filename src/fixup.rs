@@ -0,0 +1,287 @@
+//! Fixup target detection.
+//!
+//! A staged change that only touches lines a recent, unpushed commit wrote is
+//! usually a follow-up to that commit, better recorded as `fixup! <subject>`
+//! for a later `git rebase --autosquash` than as a new message. The staged
+//! hunks' pre-image lines are blamed at `HEAD`, and the unpushed commit that
+//! last touched the most of them is the suggested target, provided that is
+//! at least `fixup_threshold` percent of the changed lines. Inserted lines
+//! count for the line above them; lines of new files have no history and
+//! only count against the share.
+
+use git2::{BlameOptions, DiffOptions, Oid, Repository, Sort};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Most recent unpushed commits considered as fixup targets.
+const MAX_CANDIDATES: usize = 50;
+
+/// The commit a staged change most likely fixes up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixupTarget {
+    /// Full commit id
+    pub id: String,
+    pub subject: String,
+    /// Changed lines last touched by this commit
+    pub matched_lines: usize,
+    /// All changed lines considered (context for how clear the match is)
+    pub total_lines: usize,
+}
+
+impl FixupTarget {
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(7)]
+    }
+
+    /// The message `git commit --fixup=<id>` would write.
+    pub fn message(&self) -> String {
+        format!("fixup! {}", self.subject)
+    }
+}
+
+/// Commits on `HEAD` that no remote-tracking branch contains, newest first,
+/// excluding merges and existing `fixup!`/`squash!` commits.
+fn unpushed_commits(repo: &Repository) -> Result<Vec<Oid>, git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push_head()?;
+    revwalk.hide_glob("refs/remotes/*")?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let subject = commit.summary().unwrap_or("");
+        if commit.parent_count() > 1
+            || subject.starts_with("fixup! ")
+            || subject.starts_with("squash! ")
+        {
+            continue;
+        }
+        commits.push(commit.id());
+        if commits.len() == MAX_CANDIDATES {
+            break;
+        }
+    }
+    Ok(commits)
+}
+
+/// Pre-image lines to blame, by file, each with the number of changed lines
+/// it stands for.
+type PreimageLines = HashMap<PathBuf, Vec<(usize, usize)>>;
+
+/// The staged changes as blame lookups: for each file, pre-image line
+/// numbers (1-based, in `HEAD`'s version) with the number of changed lines
+/// each stands for, plus the lines of new files, which have no history.
+///
+/// A modified or deleted line stands for itself; the extra lines of a hunk
+/// that grows, and a pure insertion, count for the old line just above them.
+fn staged_preimage_lines(repo: &Repository) -> Result<(PreimageLines, usize), git2::Error> {
+    let head_tree = repo.head()?.peel_to_tree()?;
+    let mut opts = DiffOptions::new();
+    opts.context_lines(0);
+    let diff = repo.diff_tree_to_index(Some(&head_tree), None, Some(&mut opts))?;
+
+    let mut lines = PreimageLines::new();
+    let mut new_file_lines = 0;
+    diff.foreach(
+        &mut |_, _| true,
+        None,
+        Some(&mut |delta, hunk| {
+            let old_lines = hunk.old_lines() as usize;
+            let new_lines = hunk.new_lines() as usize;
+            let Some(path) = delta
+                .old_file()
+                .path()
+                .filter(|_| !delta.old_file().id().is_zero())
+            else {
+                new_file_lines += new_lines;
+                return true;
+            };
+            let start = hunk.old_start() as usize;
+            let touched = lines.entry(path.to_path_buf()).or_default();
+            if old_lines == 0 {
+                // Inserted after line `start` (0 for the top of the file)
+                touched.push((start.max(1), new_lines));
+            } else {
+                touched.extend((start..start + old_lines).map(|line| (line, 1)));
+                if new_lines > old_lines {
+                    touched.push((start + old_lines - 1, new_lines - old_lines));
+                }
+            }
+            true
+        }),
+        None,
+    )?;
+    Ok((lines, new_file_lines))
+}
+
+/// Suggest the unpushed commit that last touched the most of the staged
+/// changed lines, or `None` when none touched at least `threshold` percent
+/// of them.
+pub fn find_fixup_target(
+    repo: &Repository,
+    threshold: u16,
+) -> Result<Option<FixupTarget>, git2::Error> {
+    let candidates = unpushed_commits(repo)?;
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    let head = repo.head()?.peel_to_commit()?.id();
+
+    let mut counts: HashMap<Oid, usize> = HashMap::new();
+    let (preimage, mut total_lines) = staged_preimage_lines(repo)?;
+    for (path, lines) in preimage {
+        let mut opts = BlameOptions::new();
+        opts.newest_commit(head);
+        // A path missing at HEAD (renamed in the index) has nothing to blame.
+        let Ok(blame) = repo.blame_file(Path::new(&path), Some(&mut opts)) else {
+            continue;
+        };
+        for (line, weight) in lines {
+            let Some(hunk) = blame.get_line(line) else {
+                continue;
+            };
+            total_lines += weight;
+            let id = hunk.final_commit_id();
+            if candidates.contains(&id) {
+                *counts.entry(id).or_default() += weight;
+            }
+        }
+    }
+
+    // Most matched lines wins; ties go to the most recent commit.
+    let best = candidates
+        .iter()
+        .filter_map(|id| counts.get(id).map(|&n| (*id, n)))
+        .fold(None, |best: Option<(Oid, usize)>, (id, n)| match best {
+            Some((_, best_n)) if best_n >= n => best,
+            _ => Some((id, n)),
+        });
+
+    Ok(match best {
+        Some((id, matched_lines)) if matched_lines * 100 >= threshold as usize * total_lines => {
+            let commit = repo.find_commit(id)?;
+            Some(FixupTarget {
+                id: id.to_string(),
+                subject: commit.summary().unwrap_or("").to_string(),
+                matched_lines,
+                total_lines,
+            })
+        }
+        _ => None,
+    })
+}
+
+/// The fixup target for an explicit revision (`--fixup=<rev>`).
+pub fn resolve_fixup_target(repo: &Repository, rev: &str) -> Result<FixupTarget, git2::Error> {
+    let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+    Ok(FixupTarget {
+        id: commit.id().to_string(),
+        subject: commit.summary().unwrap_or("").to_string(),
+        matched_lines: 0,
+        total_lines: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_file(repo: &Repository, name: &str, content: &str, message: &str) -> Oid {
+        stage_file(repo, name, content);
+        let mut index = repo.index().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let parent = repo.head().and_then(|h| h.peel_to_commit()).ok();
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    fn stage_file(repo: &Repository, name: &str, content: &str) {
+        fs::write(repo.workdir().unwrap().join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+    }
+
+    #[test]
+    fn test_finds_commit_that_wrote_the_lines() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "one\ntwo\n", "init");
+        let login = commit_file(
+            &repo,
+            "a.txt",
+            "one\ntwo\nfn login() {\n    check();\n}\n",
+            "feat: add login",
+        );
+        commit_file(&repo, "b.txt", "other\n", "docs: notes");
+
+        // Fix a typo inside the login function.
+        stage_file(
+            &repo,
+            "a.txt",
+            "one\ntwo\nfn login() {\n    check_user();\n}\n",
+        );
+        let target = find_fixup_target(&repo, 50).unwrap().unwrap();
+        assert_eq!(target.id, login.to_string());
+        assert_eq!(target.message(), "fixup! feat: add login");
+        assert_eq!((target.matched_lines, target.total_lines), (1, 1));
+    }
+
+    #[test]
+    fn test_no_target_for_pushed_or_new_lines() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let init = commit_file(&repo, "a.txt", "one\n", "init");
+        repo.reference("refs/remotes/origin/main", init, true, "")
+            .unwrap();
+        commit_file(&repo, "b.txt", "two\n", "feat: b");
+
+        // Only touches a line from the pushed commit.
+        stage_file(&repo, "a.txt", "uno\n");
+        assert!(find_fixup_target(&repo, 50).unwrap().is_none());
+
+        // A brand-new file has no pre-image at all.
+        stage_file(&repo, "a.txt", "one\n");
+        stage_file(&repo, "c.txt", "new\n");
+        assert!(find_fixup_target(&repo, 50).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mostly_new_change_is_not_a_fixup() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let init = commit_file(&repo, "a.txt", "one\ntwo\nthree\n", "init");
+        repo.reference("refs/remotes/origin/main", init, true, "")
+            .unwrap();
+        commit_file(&repo, "a.txt", "one\n2\nthree\n", "fix: numeral");
+
+        // Touches the unpushed commit's line once, among 20 new lines added
+        // below a pushed one.
+        let added = "new\n".repeat(20);
+        stage_file(&repo, "a.txt", &format!("one\nII\nthree\n{}", added));
+        assert!(find_fixup_target(&repo, 50).unwrap().is_none());
+
+        let target = find_fixup_target(&repo, 1).unwrap().unwrap();
+        assert_eq!((target.matched_lines, target.total_lines), (1, 21));
+
+        // Lines added to the commit's own line count for it.
+        stage_file(&repo, "a.txt", "one\nII\nii\nthree\n");
+        let target = find_fixup_target(&repo, 50).unwrap().unwrap();
+        assert_eq!((target.matched_lines, target.total_lines), (2, 2));
+    }
+
+    #[test]
+    fn test_resolve_explicit_target() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "one\n", "feat: first");
+        commit_file(&repo, "a.txt", "two\n", "feat: second");
+        let target = resolve_fixup_target(&repo, "HEAD~1").unwrap();
+        assert_eq!(target.message(), "fixup! feat: first");
+    }
+}
@@ -1,24 +1,182 @@
 use colored::*;
-use git2::{Error as GitError, Repository, Sort};
+use git2::{Error as GitError, Oid, Repository, Sort};
+use serde::Serialize;
 use std::cmp;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::Path;
 
-use crate::cmtignore::matches_pattern;
+use crate::budget::{self, BudgetReport};
+use crate::cmtignore::Cmtignore;
+use crate::lockfile::{DependencyChange, Lockfile};
+use crate::outline::{Language, Outline};
+use crate::skip::{looks_binary, SkipCandidate, SkipConfig, SkipRules};
+use crate::submodule::SubmoduleUpdate;
 
 /// Stats about staged changes for display
-#[derive(Debug, Clone)]
+///
+/// Files are listed as [`FileStat`]s; they used to be
+/// `(path, insertions, deletions)` tuples.
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct DiffStats {
     pub files_changed: usize,
     pub insertions: usize,
     pub deletions: usize,
-    pub file_changes: Vec<(String, usize, usize)>, // (filename, adds, dels)
-    pub skipped_files: Vec<(String, usize, usize)>, // Files exceeding max_file_lines threshold
-    pub ignored_files: Vec<(String, usize, usize)>, // Files matched by .cmtignore
+    pub file_changes: Vec<FileStat>,
+    pub skipped_files: Vec<FileStat>, // Files exceeding max_file_lines threshold
+    pub ignored_files: Vec<FileStat>, // Skip rules, .cmtignore'd or .gitattributes-excluded
+    /// Package bumps, additions and removals read from excluded lockfiles
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependency_changes: Vec<DependencyChange>,
+    /// Upstream commits behind each submodule pointer change
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub submodule_updates: Vec<SubmoduleUpdate>,
+    /// Files trimmed or left out of the diff text to fit the token budget
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<BudgetReport>,
     pub has_unstaged: bool,
 }
 
+/// How a file changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    #[default]
+    Modified,
+    /// Moved (and possibly edited); `FileStat::old_path` is the source
+    Renamed,
+    /// Copied from `FileStat::old_path` (and possibly edited)
+    Copied,
+    /// Only the file mode changed (e.g. made executable)
+    ModeChange,
+    /// Binary content changed; there are no lines to show
+    Binary,
+    /// A submodule's pinned commit changed
+    Submodule,
+}
+
+impl ChangeKind {
+    /// The kind of a delta whose patch has been loaded (binary detection
+    /// needs the content).
+    fn from_delta(delta: &git2::DiffDelta) -> Self {
+        let (old, new) = (delta.old_file(), delta.new_file());
+        if old.mode() == git2::FileMode::Commit || new.mode() == git2::FileMode::Commit {
+            return ChangeKind::Submodule;
+        }
+        match delta.status() {
+            git2::Delta::Renamed => ChangeKind::Renamed,
+            git2::Delta::Copied => ChangeKind::Copied,
+            git2::Delta::Added | git2::Delta::Untracked => ChangeKind::Added,
+            git2::Delta::Deleted => ChangeKind::Deleted,
+            _ if delta.flags().is_binary() => ChangeKind::Binary,
+            _ if old.mode() != new.mode() => ChangeKind::ModeChange,
+            _ => ChangeKind::Modified,
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Deleted => write!(f, "deleted"),
+            ChangeKind::Modified => write!(f, "modified"),
+            ChangeKind::Renamed => write!(f, "renamed"),
+            ChangeKind::Copied => write!(f, "copied"),
+            ChangeKind::ModeChange => write!(f, "mode change"),
+            ChangeKind::Binary => write!(f, "binary"),
+            ChangeKind::Submodule => write!(f, "submodule"),
+        }
+    }
+}
+
+/// Per-file line counts and change kind, with why the file was left out of the
+/// prompt (for ignored and skipped files).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileStat {
+    pub path: String,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub kind: ChangeKind,
+    /// Source path of a rename or copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    /// `old -> new` octal modes, when the mode changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Functions, methods and types the changes fall in (`--function-context`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl FileStat {
+    pub fn new(path: &str, insertions: usize, deletions: usize) -> Self {
+        Self {
+            path: path.to_string(),
+            insertions,
+            deletions,
+            kind: ChangeKind::default(),
+            old_path: None,
+            mode: None,
+            symbols: Vec::new(),
+            reason: None,
+        }
+    }
+
+    /// Kind, rename/copy source and mode change from a loaded patch's delta.
+    fn with_delta(mut self, delta: &git2::DiffDelta) -> Self {
+        self.kind = ChangeKind::from_delta(delta);
+        if matches!(self.kind, ChangeKind::Renamed | ChangeKind::Copied) {
+            self.old_path = delta
+                .old_file()
+                .path()
+                .map(|p| p.to_string_lossy().to_string());
+        }
+        let (old_mode, new_mode) = (delta.old_file().mode(), delta.new_file().mode());
+        let unreadable = |mode| matches!(mode, git2::FileMode::Unreadable);
+        if old_mode != new_mode && !unreadable(old_mode) && !unreadable(new_mode) {
+            self.mode = Some(format!(
+                "{:o} -> {:o}",
+                u32::from(old_mode),
+                u32::from(new_mode)
+            ));
+        }
+        self
+    }
+
+    /// The change kind with its details, e.g. `renamed from src/old.rs` or
+    /// `mode change 100644 -> 100755`.
+    pub fn describe_kind(&self) -> String {
+        let mut description = self.kind.to_string();
+        if let Some(old_path) = &self.old_path {
+            description.push_str(&format!(" from {}", old_path));
+        }
+        if let Some(mode) = &self.mode {
+            match self.kind {
+                ChangeKind::ModeChange => description = format!("mode change {}", mode),
+                _ => description.push_str(&format!(", mode {}", mode)),
+            }
+        }
+        description
+    }
+
+    fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
 impl DiffStats {
+    /// Diffs this large skip the recent-commit context (very high thresholds;
+    /// Gemini supports 1M tokens).
+    pub fn is_very_large(&self) -> bool {
+        self.files_changed > 150 || (self.insertions + self.deletions) > 50000
+    }
+
     /// Print the stats in a compact format
     pub fn print(&self) {
         println!();
@@ -43,16 +201,16 @@ impl DiffStats {
             .collect();
 
         // Calculate column widths
-        let max_name_len = all_files.iter().map(|(f, _, _)| f.len()).max().unwrap_or(0);
+        let max_name_len = all_files.iter().map(|f| f.path.len()).max().unwrap_or(0);
         let max_adds = all_files
             .iter()
-            .map(|(_, a, _)| *a)
+            .map(|f| f.insertions)
             .max()
             .unwrap_or(0)
             .max(self.insertions);
         let max_dels = all_files
             .iter()
-            .map(|(_, _, d)| *d)
+            .map(|f| f.deletions)
             .max()
             .unwrap_or(0)
             .max(self.deletions);
@@ -106,8 +264,15 @@ impl DiffStats {
             .red()
         );
 
-        // Print regular file list
-        for (file, adds, dels) in &self.file_changes {
+        // Print regular file list, noting renames, copies, mode changes,
+        // binaries and submodules (which +/- counts don't convey)
+        for stat in &self.file_changes {
+            let FileStat {
+                path: file,
+                insertions: adds,
+                deletions: dels,
+                ..
+            } = stat;
             print!("  {:<width$}", file.white(), width = max_name_len + 4);
             if *adds > 0 {
                 print!(
@@ -117,20 +282,42 @@ impl DiffStats {
             } else {
                 print!("{:>width$}", "", width = adds_width + 1);
             }
+            let notable = !matches!(
+                stat.kind,
+                ChangeKind::Modified | ChangeKind::Added | ChangeKind::Deleted
+            );
             if *dels > 0 {
                 // Print spacing separately to avoid ANSI code interference
                 print!("   ");
-                println!(
+                print!(
                     "{}",
                     format!("{:>width$}", format!("-{}", dels), width = dels_width + 1).red()
                 );
-            } else {
-                println!();
+            } else if notable {
+                print!("{:>width$}", "", width = dels_width + 4);
             }
+            if notable {
+                print!("{}", format!("  {}", stat.describe_kind()).cyan());
+            }
+            if !stat.symbols.is_empty() {
+                if *dels == 0 && !notable {
+                    print!("{:>width$}", "", width = dels_width + 4);
+                }
+                print!("{}", format!("  {}", symbol_list(&stat.symbols)).dimmed());
+            }
+            println!();
         }
 
-        // Print ignored files (auto-skipped + .cmtignore) - dimmed with ~ marker
-        for (file, adds, dels) in &self.ignored_files {
+        // Print ignored files (skip rules, .cmtignore, .gitattributes) and
+        // skipped files (exceeding threshold) - dimmed with ~ and the reason
+        for FileStat {
+            path: file,
+            insertions: adds,
+            deletions: dels,
+            reason,
+            ..
+        } in self.ignored_files.iter().chain(&self.skipped_files)
+        {
             print!("  {:<width$}", file.dimmed(), width = max_name_len + 4);
             if *adds > 0 {
                 print!(
@@ -149,41 +336,146 @@ impl DiffStats {
             } else {
                 print!("{:>width$}", "", width = dels_width + 4);
             }
-            println!("{}", "  ~".dimmed());
+            match reason {
+                Some(reason) => println!("{}", format!("  ~ {}", reason).dimmed()),
+                None => println!("{}", "  ~".dimmed()),
+            }
         }
 
-        // Print skipped files (exceeding threshold) - dimmed with ~ marker
-        for (file, adds, dels) in &self.skipped_files {
-            print!("  {:<width$}", file.dimmed(), width = max_name_len + 4);
-            if *adds > 0 {
-                print!(
-                    "{}",
-                    format!("{:>width$}", format!("+{}", adds), width = adds_width + 1).dimmed()
-                );
-            } else {
-                print!("{:>width$}", "", width = adds_width + 1);
+        // Dependency changes read from the lockfiles above
+        if !self.dependency_changes.is_empty() {
+            println!("{}", "  Dependencies:".dimmed());
+            for change in self
+                .dependency_changes
+                .iter()
+                .take(MAX_PRINTED_DEPENDENCIES)
+            {
+                println!("{}", format!("    {}", change).dimmed());
             }
-            if *dels > 0 {
-                print!("   ");
-                print!(
+            let more = self
+                .dependency_changes
+                .len()
+                .saturating_sub(MAX_PRINTED_DEPENDENCIES);
+            if more > 0 {
+                println!("{}", format!("    ... and {} more", more).dimmed());
+            }
+        }
+
+        // Upstream history of submodule bumps
+        if !self.submodule_updates.is_empty() {
+            println!("{}", "  Submodules:".dimmed());
+            for update in &self.submodule_updates {
+                println!("{}", format!("    {}", update).dimmed());
+            }
+        }
+
+        // Files cut from the diff text to fit the token budget
+        if let Some(budget) = &self.budget {
+            println!(
+                "{}",
+                format!(
+                    "  Token budget (~{} of {} tokens used):",
+                    budget.used_tokens, budget.max_tokens
+                )
+                .yellow()
+            );
+            for file in budget.cut_files.iter().take(MAX_PRINTED_DEPENDENCIES) {
+                println!(
                     "{}",
-                    format!("{:>width$}", format!("-{}", dels), width = dels_width + 1).dimmed()
+                    format!("    {} ({})", file.path, file.describe()).dimmed()
                 );
-            } else {
-                print!("{:>width$}", "", width = dels_width + 4);
             }
-            println!("{}", "  ~".dimmed());
+            let more = budget
+                .cut_files
+                .len()
+                .saturating_sub(MAX_PRINTED_DEPENDENCIES);
+            if more > 0 {
+                println!("{}", format!("    ... and {} more", more).dimmed());
+            }
         }
 
         println!(); // Space before next section
     }
 }
 
+/// Changed symbols listed per file in the stats before the rest are elided.
+const MAX_PRINTED_SYMBOLS: usize = 3;
+
+/// `a, b, c +2 more`
+fn symbol_list(symbols: &[String]) -> String {
+    let mut list = symbols
+        .iter()
+        .take(MAX_PRINTED_SYMBOLS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if symbols.len() > MAX_PRINTED_SYMBOLS {
+        list.push_str(&format!(" +{} more", symbols.len() - MAX_PRINTED_SYMBOLS));
+    }
+    list
+}
+
+/// Dependency changes (and budget cuts) listed under the stats before the
+/// rest are elided.
+const MAX_PRINTED_DEPENDENCIES: usize = 10;
+
+/// Which two states of the repository to diff.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DiffSource {
+    /// Index vs `HEAD`: what `git commit` would record
+    #[default]
+    Staged,
+    /// Working tree vs index (`git diff`)
+    Unstaged,
+    /// `from` vs `to`, each any revision or tree-ish; `to: None` means the
+    /// working tree (`git diff <from>`)
+    Revisions { from: String, to: Option<String> },
+}
+
+impl fmt::Display for DiffSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffSource::Staged => write!(f, "staged changes"),
+            DiffSource::Unstaged => write!(f, "unstaged changes"),
+            DiffSource::Revisions { from, to: None } => write!(f, "{}..working tree", from),
+            DiffSource::Revisions { from, to: Some(to) } => write!(f, "{}..{}", from, to),
+        }
+    }
+}
+
+/// Limits that shape the diff text sent to the model.
+#[derive(Debug, Clone, Copy)]
+pub struct DiffSettings<'a> {
+    pub context_lines: u32,
+    pub max_lines_per_file: usize,
+    pub max_line_width: usize,
+    /// Files with more changed lines than this are skipped (0 disables)
+    pub max_file_lines: usize,
+    /// Similarity (percent) for a delete/add or add pair to count as a rename
+    /// or copy (0 disables detection)
+    pub rename_threshold: u16,
+    /// Widen hunks to their enclosing function or type where a grammar is
+    /// available, instead of `context_lines` (needs a repository)
+    pub function_context: bool,
+    /// Estimated tokens the diff text may use; files are packed by importance
+    /// when it would be larger (0 disables)
+    pub max_diff_tokens: usize,
+    /// Also return the complete diff, unfiltered and untruncated, as
+    /// [`StagedChanges::full_text`] (for `fail_on_secrets`)
+    pub full_text: bool,
+    /// Built-in and configured rules for files left out automatically
+    pub skip: &'a SkipConfig,
+    pub cmtignore: &'a Cmtignore,
+}
+
 /// Result of getting staged changes - includes both diff text and stats
 #[derive(Debug)]
 pub struct StagedChanges {
     pub diff_text: String,
     pub stats: DiffStats,
+    /// Every changed file's diff, ignored and skipped files included, without
+    /// line caps or the token budget; `None` unless `DiffSettings::full_text`
+    pub full_text: Option<String>,
 }
 
 /// Append a diff content line to `out`, truncating it to `max_width` bytes on a
@@ -207,52 +499,81 @@ fn push_diff_line(out: &mut String, content: &str, max_width: usize) {
     }
 }
 
-fn is_skippable(path: &Path) -> bool {
-    let name = path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_lowercase())
-        .unwrap_or_default();
-    let ext = path
-        .extension()
-        .map(|e| e.to_string_lossy().to_lowercase())
-        .unwrap_or_default();
-    let path_str = path.to_string_lossy().to_lowercase();
-
-    // Lock and dependency snapshot files
-    if ext == "lock"
-        || name == "package-lock.json"
-        || name == "pnpm-lock.yaml"
-        || name == "yarn.lock"
-        || name == "cargo.lock"
-    {
-        return true;
-    }
-
-    // Large generated / compiled assets commonly not useful for commit intent
-    if ext == "map" || ext == "min.js" || ext == "min.css" {
-        return true;
-    }
-
-    // Binary/media assets that bloat prompts; the model can't interpret them meaningfully
-    if matches!(
-        ext.as_str(),
-        "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "bmp" | "ico" | "svg"
-    ) {
-        return true;
+/// Whether a changed file's content looks binary: the new version's blob (the
+/// old one for deletions), or the work-tree file when the blob isn't stored.
+fn sniff_binary(repo: &Repository, delta: &git2::DiffDelta, path: &Path) -> bool {
+    let file = if delta.new_file().id().is_zero() && delta.status() == git2::Delta::Deleted {
+        delta.old_file()
+    } else {
+        delta.new_file()
+    };
+    if let Ok(blob) = repo.find_blob(file.id()) {
+        return looks_binary(blob.content());
     }
+    sniff_workdir_file(repo, path)
+}
 
-    // Skip obvious build artifacts if staged
-    if path_str.starts_with("dist/") || path_str.starts_with("build/") {
-        return true;
+/// Whether the work-tree file at `path` looks binary (false if unreadable).
+fn sniff_workdir_file(repo: &Repository, path: &Path) -> bool {
+    let Some(workdir) = repo.workdir() else {
+        return false;
+    };
+    let mut head = Vec::new();
+    match std::fs::File::open(workdir.join(path)) {
+        Ok(f) => {
+            use std::io::Read;
+            let _ = f.take(8000).read_to_end(&mut head);
+            looks_binary(&head)
+        }
+        Err(_) => false,
     }
+}
 
-    false
+/// `.gitattributes` attributes that keep a file out of the prompt, with the
+/// value that triggers them: `cmt-ignore`, `linguist-generated`,
+/// `linguist-vendored`, `binary`, or `-diff`.
+const IGNORE_ATTRIBUTES: &[(&str, bool)] = &[
+    ("cmt-ignore", true),
+    ("linguist-generated", true),
+    ("linguist-vendored", true),
+    ("binary", true),
+    ("diff", false),
+];
+
+/// The `.gitattributes` attribute excluding `path` from the prompt, if any.
+fn attribute_reason(repo: &Repository, path: &Path) -> Option<String> {
+    IGNORE_ATTRIBUTES.iter().find_map(|&(name, wanted)| {
+        let value = repo
+            .get_attr(path, name, git2::AttrCheckFlags::FILE_THEN_INDEX)
+            .ok()?;
+        let set = match git2::AttrValue::from_string(value) {
+            git2::AttrValue::True => Some(true),
+            git2::AttrValue::False => Some(false),
+            git2::AttrValue::String(v) if v.eq_ignore_ascii_case("true") => Some(true),
+            git2::AttrValue::String(v) if v.eq_ignore_ascii_case("false") => Some(false),
+            _ => None,
+        }?;
+        (set == wanted).then(|| {
+            let attr = if wanted {
+                name.to_string()
+            } else {
+                format!("-{}", name)
+            };
+            format!(".gitattributes: {}", attr)
+        })
+    })
 }
 
 pub fn get_recent_commits(repo: &Repository, count: usize) -> Result<String, GitError> {
+    let head = repo.head()?.peel_to_commit()?.id();
+    get_commits_from(repo, head, count)
+}
+
+/// Like [`get_recent_commits`], but walking back from `start` instead of HEAD.
+pub fn get_commits_from(repo: &Repository, start: Oid, count: usize) -> Result<String, GitError> {
     let mut revwalk = repo.revwalk()?;
     revwalk.set_sorting(Sort::TIME)?;
-    revwalk.push_head()?;
+    revwalk.push(start)?;
 
     let mut commit_messages = String::new();
 
@@ -271,6 +592,95 @@ pub fn get_recent_commits(repo: &Repository, count: usize) -> Result<String, Git
     Ok(commit_messages)
 }
 
+/// A commit on a branch being squashed.
+#[derive(Debug, Clone, Serialize)]
+pub struct BranchCommit {
+    /// Abbreviated commit id
+    pub id: String,
+    pub message: String,
+    pub author_name: String,
+    pub author_email: String,
+}
+
+/// The commits `HEAD` adds on top of its merge base with another branch.
+#[derive(Debug, Clone)]
+pub struct BranchRange {
+    pub merge_base: Oid,
+    pub head: Oid,
+    /// Non-merge commits from the merge base to `HEAD`, oldest first
+    pub commits: Vec<BranchCommit>,
+}
+
+/// Collect the commits `HEAD` has that `base` doesn't (`git log base..HEAD`),
+/// skipping merge commits (e.g. merges of `base` into the branch).
+pub fn get_branch_commits(repo: &Repository, base: &str) -> Result<BranchRange, GitError> {
+    let base_commit = repo
+        .revparse_single(base)
+        .and_then(|obj| obj.peel_to_commit())
+        .map_err(|e| {
+            GitError::from_str(&format!("Unknown revision '{}': {}", base, e.message()))
+        })?;
+    let head = repo.head()?.peel_to_commit()?.id();
+    let merge_base = repo.merge_base(base_commit.id(), head)?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    revwalk.push(head)?;
+    revwalk.hide(merge_base)?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let author = commit.author();
+        commits.push(BranchCommit {
+            id: commit
+                .as_object()
+                .short_id()?
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            message: commit.message().unwrap_or("").trim_end().to_string(),
+            author_name: author.name().unwrap_or("").to_string(),
+            author_email: author.email().unwrap_or("").to_string(),
+        });
+    }
+
+    Ok(BranchRange {
+        merge_base,
+        head,
+        commits,
+    })
+}
+
+/// Distinct `(name, email)` authors of the last `count` commits, most recent
+/// first (deduplicated by email).
+pub fn get_recent_authors(
+    repo: &Repository,
+    count: usize,
+) -> Result<Vec<(String, String)>, GitError> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+    revwalk.push_head()?;
+
+    let mut authors: Vec<(String, String)> = Vec::new();
+    for oid in revwalk.take(count).flatten() {
+        if let Ok(commit) = repo.find_commit(oid) {
+            let author = commit.author();
+            let (Some(name), Some(email)) = (author.name(), author.email()) else {
+                continue;
+            };
+            if !authors.iter().any(|(_, e)| e.eq_ignore_ascii_case(email)) {
+                authors.push((name.to_string(), email.to_string()));
+            }
+        }
+    }
+
+    Ok(authors)
+}
+
 /// Get the current branch name
 pub fn get_current_branch(repo: &Repository) -> Option<String> {
     repo.head().ok().and_then(|head| {
@@ -321,33 +731,290 @@ pub fn get_staged_changes(
     max_file_lines: usize,
     cmtignore_patterns: &[String],
 ) -> Result<StagedChanges, GitError> {
-    let mut opts = git2::DiffOptions::new();
-    opts.context_lines(context_lines);
+    let settings = DiffSettings {
+        context_lines,
+        max_lines_per_file,
+        max_line_width,
+        max_file_lines,
+        rename_threshold: crate::config::defaults::RENAME_THRESHOLD,
+        function_context: false,
+        max_diff_tokens: 0,
+        full_text: false,
+        skip: &SkipConfig::default(),
+        cmtignore: &Cmtignore::from_patterns(cmtignore_patterns),
+    };
+    let changes = get_changes(repo, &DiffSource::Staged, &settings)?;
+
+    if changes.stats.files_changed == 0 {
+        Err(GitError::from_str("No changes have been staged for commit"))
+    } else {
+        Ok(changes)
+    }
+}
 
-    let tree = match repo.head().and_then(|head| head.peel_to_tree()) {
-        Ok(tree) => tree,
-        Err(_) => {
+/// The tree `rev` points to, or the empty tree for an unborn `HEAD`.
+fn resolve_tree<'r>(repo: &'r Repository, rev: &str) -> Result<git2::Tree<'r>, GitError> {
+    match repo.revparse_single(rev).and_then(|obj| obj.peel_to_tree()) {
+        Ok(tree) => Ok(tree),
+        Err(_) if rev == "HEAD" && repo.head().is_err() => {
             // If there's no HEAD (new repo), use an empty tree
             repo.treebuilder(None)
                 .and_then(|builder| builder.write())
                 .and_then(|oid| repo.find_tree(oid))
-                .map_err(|e| GitError::from_str(&format!("Failed to create empty tree: {}", e)))?
+                .map_err(|e| GitError::from_str(&format!("Failed to create empty tree: {}", e)))
+        }
+        Err(e) => Err(GitError::from_str(&format!(
+            "Unknown revision '{}': {}",
+            rev,
+            e.message()
+        ))),
+    }
+}
+
+/// Diff `source` and summarize it with the same filtering, truncation and
+/// stats as staged changes. `diff_text` is empty when nothing changed (or
+/// every changed file is ignored or skipped; `stats` still lists them).
+pub fn get_changes(
+    repo: &Repository,
+    source: &DiffSource,
+    settings: &DiffSettings,
+) -> Result<StagedChanges, GitError> {
+    let (old_tree, new_tree) = match source {
+        DiffSource::Staged => (Some(resolve_tree(repo, "HEAD")?), None),
+        DiffSource::Unstaged => (None, None),
+        DiffSource::Revisions { from, to } => (
+            Some(resolve_tree(repo, from)?),
+            to.as_deref().map(|to| resolve_tree(repo, to)).transpose()?,
+        ),
+    };
+
+    summarize_diff(
+        |context_lines| {
+            let mut opts = git2::DiffOptions::new();
+            opts.context_lines(context_lines);
+            let diff = match source {
+                DiffSource::Staged => {
+                    repo.diff_tree_to_index(old_tree.as_ref(), None, Some(&mut opts))
+                }
+                DiffSource::Unstaged => repo.diff_index_to_workdir(None, Some(&mut opts)),
+                DiffSource::Revisions { to: None, .. } => {
+                    repo.diff_tree_to_workdir_with_index(old_tree.as_ref(), Some(&mut opts))
+                }
+                DiffSource::Revisions { to: Some(_), .. } => {
+                    repo.diff_tree_to_tree(old_tree.as_ref(), new_tree.as_ref(), Some(&mut opts))
+                }
+            };
+            let mut diff = diff.map_err(|e| {
+                GitError::from_str(&format!("Failed to get repository diff: {}", e))
+            })?;
+            detect_renames(&mut diff, settings.rename_threshold)?;
+            Ok(diff)
+        },
+        settings,
+        matches!(source, DiffSource::Staged) && has_unstaged_changes(repo),
+        Some(repo),
+    )
+}
+
+/// Pair deleted/added files into renames and copies (`git diff -M -C`).
+fn detect_renames(diff: &mut git2::Diff, threshold: u16) -> Result<(), GitError> {
+    if threshold == 0 {
+        return Ok(());
+    }
+    let mut opts = git2::DiffFindOptions::new();
+    opts.renames(true)
+        .copies(true)
+        .rename_threshold(threshold)
+        .copy_threshold(threshold);
+    diff.find_similar(Some(&mut opts))
+        .map_err(|e| GitError::from_str(&format!("Failed to detect renames: {}", e)))
+}
+
+/// Summarize a unified diff (an emailed patch, `git diff` output, another
+/// VCS's diff) exactly like staged changes, without needing a repository.
+///
+/// The patch's own context lines are kept; `settings.context_lines` only
+/// applies to diffs cmt generates itself.
+pub fn get_patch_changes(patch: &[u8], settings: &DiffSettings) -> Result<StagedChanges, GitError> {
+    let patch = to_git_patch(&String::from_utf8_lossy(patch));
+    let parse = |_| {
+        git2::Diff::from_buffer(patch.as_bytes())
+            .map_err(|e| GitError::from_str(&format!("Failed to parse diff: {}", e.message())))
+    };
+    let changes = summarize_diff(parse, settings, false, None)?;
+
+    if changes.stats.files_changed == 0 {
+        Err(GitError::from_str("The diff contains no file changes"))
+    } else {
+        Ok(changes)
+    }
+}
+
+/// Parse `@@ -a,b +c,d @@` into the (old, new) line counts; a missing count is 1.
+fn hunk_line_counts(header: &str) -> Option<(usize, usize)> {
+    let mut ranges = header.strip_prefix("@@ ")?.split_whitespace();
+    let count = |range: &str| match range.split_once(',') {
+        Some((_, n)) => n.parse().ok(),
+        None => Some(1),
+    };
+    let old = count(ranges.next()?.strip_prefix('-')?)?;
+    let new = count(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// libgit2 only parses git-style patches. Rewrite a plain unified diff
+/// (`diff -u`, svn, hg) into one: give each `---`/`+++` pair a
+/// `diff --git` header, drop timestamps and tool-specific preamble lines.
+/// Git patches are returned unchanged.
+fn to_git_patch(text: &str) -> String {
+    if text.lines().any(|l| l.starts_with("diff --git ")) {
+        return text.to_string();
+    }
+
+    // `--- a/src/x.rs\t2024-01-01 ...` -> `src/x.rs`; None for /dev/null
+    let path = |line: &str, prefix: &str| {
+        let raw = line[4..].split('\t').next().unwrap_or("").trim();
+        (raw != "/dev/null").then(|| raw.strip_prefix(prefix).unwrap_or(raw).to_string())
+    };
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")) {
+            let old = path(line, "a/");
+            let new = path(lines[i + 1], "b/");
+            let name = new.as_deref().or(old.as_deref()).unwrap_or_default();
+            out.push_str(&format!(
+                "diff --git a/{} b/{}\n",
+                old.as_deref().unwrap_or(name),
+                new.as_deref().unwrap_or(name)
+            ));
+            match (&old, &new) {
+                (None, _) => out.push_str("new file mode 100644\n--- /dev/null\n"),
+                (_, None) => out.push_str("deleted file mode 100644\n--- a/"),
+                _ => out.push_str("--- a/"),
+            }
+            if let Some(old) = &old {
+                out.push_str(&format!("{}\n", old));
+            }
+            match &new {
+                Some(new) => out.push_str(&format!("+++ b/{}\n", new)),
+                None => out.push_str("+++ /dev/null\n"),
+            }
+            i += 2;
+            continue;
+        }
+        if let Some((mut old, mut new)) = hunk_line_counts(line) {
+            out.push_str(line);
+            out.push('\n');
+            i += 1;
+            // Copy exactly the hunk body, so a removed line starting with
+            // "--" is never mistaken for the next file header.
+            while i < lines.len() && (old > 0 || new > 0 || lines[i].starts_with('\\')) {
+                let body = lines[i];
+                match body.chars().next() {
+                    Some('-') => old = old.saturating_sub(1),
+                    Some('+') => new = new.saturating_sub(1),
+                    Some('\\') => {}
+                    _ => {
+                        old = old.saturating_sub(1);
+                        new = new.saturating_sub(1);
+                    }
+                }
+                out.push_str(body);
+                out.push('\n');
+                i += 1;
+            }
+            continue;
         }
+        // Preamble (`Index:`, `=====`, `diff -r ...`) carries nothing git needs.
+        i += 1;
+    }
+    out
+}
+
+/// Build the stats and model-facing diff text from a diff.
+///
+/// Why a changed file is left out of the prompt, checking in order: skip
+/// rules, `.cmtignore` (and the personal ignore lists), then `.gitattributes`
+/// when a repository is available.
+fn ignore_reason(
+    skip_rules: &SkipRules,
+    cmtignore: &Cmtignore,
+    attributes: Option<&Repository>,
+    file: &SkipCandidate,
+) -> Option<String> {
+    skip_rules
+        .reason(file)
+        .or_else(|| cmtignore.reason(file.path))
+        .or_else(|| attributes.and_then(|repo| attribute_reason(repo, Path::new(file.path))))
+}
+
+/// Why `path` (relative to the work tree, `/`-separated) would be left out of
+/// the prompt if it changed, judged from the work-tree file (`cmt ignore
+/// check`). `None` when it would be shown.
+pub fn check_ignore(
+    repo: &Repository,
+    path: &str,
+    skip: &SkipConfig,
+    cmtignore: &Cmtignore,
+) -> Option<String> {
+    let size = repo
+        .workdir()
+        .and_then(|workdir| std::fs::metadata(workdir.join(path)).ok())
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    let is_binary = || sniff_workdir_file(repo, Path::new(path));
+    let candidate = SkipCandidate {
+        path,
+        size,
+        is_binary: &is_binary,
     };
+    ignore_reason(&SkipRules::new(skip), cmtignore, Some(repo), &candidate)
+}
+
+/// `make_diff` produces the diff with the requested number of context lines;
+/// it is called again with tighter context for very large diffs. With a
+/// repository, `.gitattributes` can exclude files too.
+fn summarize_diff<'r>(
+    make_diff: impl Fn(u32) -> Result<git2::Diff<'r>, GitError>,
+    settings: &DiffSettings,
+    has_unstaged: bool,
+    attributes: Option<&Repository>,
+) -> Result<StagedChanges, GitError> {
+    let DiffSettings {
+        context_lines,
+        max_lines_per_file,
+        max_line_width,
+        max_file_lines,
+        function_context,
+        max_diff_tokens,
+        full_text,
+        skip,
+        cmtignore,
+        ..
+    } = *settings;
+    let skip_rules = SkipRules::new(skip);
 
     // First pass: build diff and get stats
-    let diff = repo
-        .diff_tree_to_index(Some(&tree), None, Some(&mut opts))
-        .map_err(|e| GitError::from_str(&format!("Failed to get repository diff: {}", e)))?;
+    let diff = make_diff(context_lines)?;
+    let full_text = if full_text {
+        Some(full_diff_text(&diff)?)
+    } else {
+        None
+    };
 
     // Get stats (for reference, though we calculate our own for accurate filtering)
     let _git_stats = diff.stats()?;
 
     // Collect per-file stats using Patch API for accurate line counts
     // Separate into regular files, ignored files, and skipped files
-    let mut file_changes: Vec<(String, usize, usize)> = Vec::new();
-    let mut ignored_files: Vec<(String, usize, usize)> = Vec::new();
-    let mut skipped_files: Vec<(String, usize, usize)> = Vec::new();
+    let mut file_changes: Vec<FileStat> = Vec::new();
+    let mut ignored_files: Vec<FileStat> = Vec::new();
+    let mut skipped_files: Vec<FileStat> = Vec::new();
+    let mut gitlinks: Vec<(String, Oid, Oid)> = Vec::new();
 
     for delta_idx in 0..diff.deltas().len() {
         if let Ok(Some(patch)) = git2::Patch::from_diff(&diff, delta_idx) {
@@ -367,23 +1034,33 @@ pub fn get_staged_changes(
             let (_, additions, deletions) = patch.line_stats().unwrap_or((0, 0, 0));
             let total_lines = additions + deletions;
 
-            // Check if file is auto-skipped (lock files, images, build artifacts)
-            let file_path_obj = Path::new(&file_path);
-            let is_auto_skipped = is_skippable(file_path_obj);
-
-            // Check if file matches any .cmtignore pattern
-            let is_ignored = cmtignore_patterns
-                .iter()
-                .any(|pattern| matches_pattern(&file_path, pattern));
+            let stat = FileStat::new(&file_path, additions, deletions).with_delta(&patch.delta());
 
-            if is_auto_skipped || is_ignored {
-                // Auto-skipped and .cmtignore files go to ignored_files
-                ignored_files.push((file_path, additions, deletions));
+            // Skip-rule matches (lockfiles, minified assets, images, build
+            // output, binaries, configured rules), .cmtignore'd and
+            // .gitattributes-excluded files go to ignored_files
+            let file_path_obj = Path::new(&file_path);
+            let delta = patch.delta();
+            let is_binary = || {
+                stat.kind == ChangeKind::Binary
+                    || attributes.is_some_and(|repo| sniff_binary(repo, &delta, file_path_obj))
+            };
+            let candidate = SkipCandidate {
+                path: &file_path,
+                size: cmp::max(delta.old_file().size(), delta.new_file().size()),
+                is_binary: &is_binary,
+            };
+            if let Some(reason) = ignore_reason(&skip_rules, cmtignore, attributes, &candidate) {
+                ignored_files.push(stat.with_reason(reason));
             } else if max_file_lines > 0 && total_lines > max_file_lines {
                 // File exceeds threshold (only check if threshold > 0)
-                skipped_files.push((file_path, additions, deletions));
+                skipped_files.push(stat.with_reason(format!("over {} lines", max_file_lines)));
             } else {
-                file_changes.push((file_path, additions, deletions));
+                if stat.kind == ChangeKind::Submodule {
+                    let delta = patch.delta();
+                    gitlinks.push((file_path, delta.old_file().id(), delta.new_file().id()));
+                }
+                file_changes.push(stat);
             }
         }
     }
@@ -393,24 +1070,60 @@ pub fn get_staged_changes(
         .iter()
         .chain(ignored_files.iter())
         .chain(skipped_files.iter())
-        .map(|(_, a, _)| a)
+        .map(|f| f.insertions)
         .sum();
     let total_deletions: usize = file_changes
         .iter()
         .chain(ignored_files.iter())
         .chain(skipped_files.iter())
-        .map(|(_, _, d)| d)
+        .map(|f| f.deletions)
         .sum();
     let total_files = file_changes.len() + ignored_files.len() + skipped_files.len();
 
-    let stats = DiffStats {
+    // Build set of files to exclude from diff text (ignored + skipped)
+    let excluded_files: HashSet<String> = ignored_files
+        .iter()
+        .chain(skipped_files.iter())
+        .map(|f| f.path.clone())
+        .collect();
+
+    // Lockfiles are excluded, but their version bumps are worth telling the
+    // model about. Parse them from a diff with enough context to see each
+    // package's name next to its changed version.
+    let dependency_changes = if excluded_files
+        .iter()
+        .any(|path| Lockfile::detect(Path::new(path)).is_some())
+    {
+        if context_lines >= LOCKFILE_CONTEXT_LINES {
+            dependency_changes(&diff, &excluded_files)
+        } else {
+            dependency_changes(&make_diff(LOCKFILE_CONTEXT_LINES)?, &excluded_files)
+        }
+    } else {
+        Vec::new()
+    };
+
+    // A submodule bump is two SHAs in the diff; its own history says what
+    // the update brings in.
+    let submodule_updates = match attributes {
+        Some(repo) => gitlinks
+            .iter()
+            .map(|(path, old, new)| SubmoduleUpdate::describe(repo, path, *old, *new))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut stats = DiffStats {
         files_changed: total_files,
         insertions: total_insertions,
         deletions: total_deletions,
         file_changes,
         skipped_files,
         ignored_files,
-        has_unstaged: has_unstaged_changes(repo),
+        dependency_changes,
+        submodule_updates,
+        budget: None,
+        has_unstaged,
     };
 
     // Adaptive trimming: only tighten for very large diffs (Gemini Flash supports 1M tokens)
@@ -429,27 +1142,63 @@ pub fn get_staged_changes(
 
     // If we tightened context lines, rebuild diff with the smaller context for printing
     let diff = if effective_context_lines != context_lines {
-        let mut opts = git2::DiffOptions::new();
-        opts.context_lines(effective_context_lines);
-        repo.diff_tree_to_index(Some(&tree), None, Some(&mut opts))
-            .map_err(|e| GitError::from_str(&format!("Failed to get repository diff: {}", e)))?
+        make_diff(effective_context_lines)?
     } else {
         diff
     };
 
-    // Build set of files to exclude from diff text (ignored + skipped)
-    let excluded_files: HashSet<String> = stats
-        .ignored_files
-        .iter()
-        .chain(stats.skipped_files.iter())
-        .map(|(f, _, _)| f.clone())
-        .collect();
+    // With function context, shown files in a supported language get hunks
+    // rebuilt from a whole-file diff; the rest keep the line-context hunks.
+    // A patch read from elsewhere only has its own context to work with.
+    let mut function_hunks: HashMap<String, String> = HashMap::new();
+    let wants_outline = |stat: &FileStat| {
+        stat.kind != ChangeKind::Binary
+            && stat.kind != ChangeKind::Submodule
+            && Language::detect(Path::new(&stat.path)).is_some()
+    };
+    if function_context && attributes.is_some() && stats.file_changes.iter().any(wants_outline) {
+        let whole = make_diff(WHOLE_FILE_CONTEXT)?;
+        for delta_idx in 0..whole.deltas().len() {
+            let Ok(Some(patch)) = git2::Patch::from_diff(&whole, delta_idx) else {
+                continue;
+            };
+            let Some(path) = patch
+                .delta()
+                .new_file()
+                .path()
+                .map(|p| p.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+            let Some(stat) = stats
+                .file_changes
+                .iter_mut()
+                .find(|stat| stat.path == path && wants_outline(stat))
+            else {
+                continue;
+            };
+            let Some(language) = Language::detect(Path::new(&path)) else {
+                continue;
+            };
+            let hunks = function_context_hunks(
+                &patch,
+                language,
+                effective_context_lines,
+                effective_max_lines_per_file,
+                max_line_width,
+            );
+            stat.symbols = hunks.symbols;
+            function_hunks.insert(path, hunks.text);
+        }
+    }
 
     // Build diff text. The line cap is applied PER FILE: we track the current
     // file and reset the counter whenever the diff moves on to a new file, so a
     // large early file can't consume the whole budget and starve later files
-    // out of the diff entirely.
+    // out of the diff entirely. Each file's start offset is recorded so the
+    // token budget can pack whole files and hunks afterwards.
     let mut diff_str = String::new();
+    let mut file_starts: Vec<(String, usize)> = Vec::new();
     let mut line_count = 0usize;
     let mut truncated = false;
     let mut current_file: Option<std::path::PathBuf> = None;
@@ -465,11 +1214,7 @@ pub fn get_staged_changes(
             current_file = Some(file_path.to_path_buf());
             line_count = 0;
             truncated = false;
-        }
-
-        // Skip .lock files and other auto-skippable files
-        if is_skippable(file_path) {
-            return true;
+            file_starts.push((file_path.to_string_lossy().to_string(), diff_str.len()));
         }
 
         // Skip files that are ignored or exceed threshold
@@ -478,6 +1223,15 @@ pub fn get_staged_changes(
             return true;
         }
 
+        // Function-context files: keep the header, swap in the rebuilt hunks
+        if let Some(hunks) = function_hunks.get(file_path_str.as_ref()) {
+            if line.origin() == 'F' {
+                diff_str.push_str(std::str::from_utf8(line.content()).unwrap_or(""));
+                diff_str.push_str(hunks);
+            }
+            return true;
+        }
+
         if line_count < effective_max_lines_per_file {
             match line.origin() {
                 '+' | '-' | ' ' => {
@@ -500,14 +1254,276 @@ pub fn get_staged_changes(
     })
     .map_err(|e| GitError::from_str(&format!("Failed to format diff: {}", e)))?;
 
-    if diff_str.is_empty() {
-        Err(GitError::from_str("No changes have been staged for commit"))
+    let diff_text = if max_diff_tokens > 0 {
+        let sections: Vec<(String, String)> = file_starts
+            .iter()
+            .enumerate()
+            .map(|(i, (path, start))| {
+                let end = file_starts
+                    .get(i + 1)
+                    .map_or(diff_str.len(), |(_, end)| *end);
+                (path.clone(), diff_str[*start..end].to_string())
+            })
+            .filter(|(_, text)| !text.is_empty())
+            .collect();
+        let (text, report) = budget::pack(&sections, max_diff_tokens);
+        stats.budget = report;
+        text
     } else {
-        Ok(StagedChanges {
-            diff_text: diff_str,
-            stats,
-        })
+        diff_str
+    };
+
+    Ok(StagedChanges {
+        diff_text,
+        stats,
+        full_text,
+    })
+}
+
+/// The complete patch text of `diff`, whatever the ignore rules, line caps
+/// and token budget would leave out. git omits binary files' content.
+fn full_diff_text(diff: &git2::Diff) -> Result<String, GitError> {
+    let mut text = String::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            text.push(line.origin());
+        }
+        text.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })
+    .map_err(|e| GitError::from_str(&format!("Failed to format diff: {}", e)))?;
+    Ok(text)
+}
+
+/// Context lines needed to pair a lockfile's changed version with its package
+/// name (the name is at most a few lines above in every supported format).
+const LOCKFILE_CONTEXT_LINES: u32 = 5;
+
+/// Dependency changes in the lockfiles among `excluded`, read from each hunk's
+/// old and new side.
+fn dependency_changes(diff: &git2::Diff, excluded: &HashSet<String>) -> Vec<DependencyChange> {
+    let mut changes: Vec<DependencyChange> = Vec::new();
+    for delta_idx in 0..diff.deltas().len() {
+        let Ok(Some(patch)) = git2::Patch::from_diff(diff, delta_idx) else {
+            continue;
+        };
+        let Some(path) = patch
+            .delta()
+            .new_file()
+            .path()
+            .or_else(|| patch.delta().old_file().path())
+            .map(Path::to_path_buf)
+        else {
+            continue;
+        };
+        let Some(lockfile) = Lockfile::detect(&path) else {
+            continue;
+        };
+        if !excluded.contains(path.to_string_lossy().as_ref()) {
+            continue;
+        }
+
+        let mut hunks = Vec::new();
+        for hunk_idx in 0..patch.num_hunks() {
+            let (mut old, mut new) = (String::new(), String::new());
+            for line_idx in 0..patch.num_lines_in_hunk(hunk_idx).unwrap_or(0) {
+                let Ok(line) = patch.line_in_hunk(hunk_idx, line_idx) else {
+                    continue;
+                };
+                let content = String::from_utf8_lossy(line.content());
+                let content = content.trim_end_matches('\n');
+                match line.origin() {
+                    ' ' => {
+                        old.push_str(content);
+                        old.push('\n');
+                        new.push_str(content);
+                        new.push('\n');
+                    }
+                    '-' => {
+                        old.push_str(content);
+                        old.push('\n');
+                    }
+                    '+' => {
+                        new.push_str(content);
+                        new.push('\n');
+                    }
+                    _ => {}
+                }
+            }
+            hunks.push((old, new));
+        }
+
+        // The same bump often shows up in several lockfiles of a monorepo.
+        for change in lockfile.hunk_changes(hunks.iter().map(|(o, n)| (o.as_str(), n.as_str()))) {
+            if !changes.contains(&change) {
+                changes.push(change);
+            }
+        }
+    }
+    changes
+}
+
+/// Context that makes a diff's single hunk span the whole file, so both
+/// versions can be reconstructed and parsed.
+const WHOLE_FILE_CONTEXT: u32 = 1_000_000;
+
+/// Definitions longer than this aren't expanded to; their changes keep line
+/// context (a one-line edit in a 2000-line class shouldn't send the class).
+const MAX_FUNCTION_CONTEXT_LINES: usize = 400;
+
+/// Hunks rebuilt around enclosing definitions, and the definitions' names.
+struct FunctionHunks {
+    text: String,
+    symbols: Vec<String>,
+}
+
+/// One line of a whole-file patch.
+struct PatchLine {
+    origin: char,
+    old: Option<usize>,
+    new: Option<usize>,
+    content: String,
+}
+
+/// Rebuild a whole-file patch's hunks so each change carries its innermost
+/// enclosing definition (in the old version for removed lines, the new version
+/// for added ones) as context. Changes outside any definition, or in one
+/// longer than [`MAX_FUNCTION_CONTEXT_LINES`], get `context_lines` instead.
+fn function_context_hunks(
+    patch: &git2::Patch,
+    language: Language,
+    context_lines: u32,
+    max_lines_per_file: usize,
+    max_line_width: usize,
+) -> FunctionHunks {
+    let mut lines = Vec::new();
+    for hunk_idx in 0..patch.num_hunks() {
+        for line_idx in 0..patch.num_lines_in_hunk(hunk_idx).unwrap_or(0) {
+            let Ok(line) = patch.line_in_hunk(hunk_idx, line_idx) else {
+                continue;
+            };
+            if !matches!(line.origin(), '+' | '-' | ' ') {
+                continue;
+            }
+            lines.push(PatchLine {
+                origin: line.origin(),
+                old: line.old_lineno().map(|n| n as usize),
+                new: line.new_lineno().map(|n| n as usize),
+                content: String::from_utf8_lossy(line.content()).to_string(),
+            });
+        }
+    }
+
+    let source = |side: fn(&PatchLine) -> Option<usize>| -> String {
+        lines
+            .iter()
+            .filter(|l| side(l).is_some())
+            .map(|l| l.content.as_str())
+            .collect()
+    };
+    let old_outline = Outline::parse(language, &source(|l| l.old));
+    let new_outline = Outline::parse(language, &source(|l| l.new));
+
+    // Which lines to keep: whole enclosing definitions, or a window of
+    // context_lines around changes outside one.
+    let mut keep = vec![false; lines.len()];
+    let mut old_ranges: Vec<(usize, usize)> = Vec::new();
+    let mut new_ranges: Vec<(usize, usize)> = Vec::new();
+    let mut symbols: Vec<String> = Vec::new();
+    let mut hunk_symbol: Vec<Option<String>> = vec![None; lines.len()];
+    let context = context_lines as usize;
+    for (idx, line) in lines.iter().enumerate() {
+        let block = match (line.origin, line.old, line.new) {
+            ('-', Some(old), _) => old_outline.enclosing(old),
+            ('+', _, Some(new)) => new_outline.enclosing(new),
+            _ => continue,
+        };
+        match block {
+            Some(block) => {
+                if !symbols.contains(&block.name) {
+                    symbols.push(block.name.clone());
+                }
+                hunk_symbol[idx] = Some(block.name.clone());
+                if block.line_count() <= MAX_FUNCTION_CONTEXT_LINES {
+                    let ranges = if line.origin == '-' {
+                        &mut old_ranges
+                    } else {
+                        &mut new_ranges
+                    };
+                    if !ranges.contains(&(block.start, block.end)) {
+                        ranges.push((block.start, block.end));
+                    }
+                    keep[idx] = true;
+                    continue;
+                }
+                mark_window(&mut keep, idx, context);
+            }
+            None => mark_window(&mut keep, idx, context),
+        }
+    }
+    let within = |n: Option<usize>, ranges: &[(usize, usize)]| {
+        n.is_some_and(|n| ranges.iter().any(|&(start, end)| start <= n && n <= end))
+    };
+    for (idx, line) in lines.iter().enumerate() {
+        if within(line.old, &old_ranges) || within(line.new, &new_ranges) {
+            keep[idx] = true;
+        }
     }
+
+    // Emit each run of kept lines as a hunk.
+    let mut text = String::new();
+    let mut line_count = 0usize;
+    let (mut last_old, mut last_new) = (0usize, 0usize);
+    let mut idx = 0;
+    while idx < lines.len() {
+        if !keep[idx] {
+            last_old = lines[idx].old.unwrap_or(last_old);
+            last_new = lines[idx].new.unwrap_or(last_new);
+            idx += 1;
+            continue;
+        }
+        let end = (idx..lines.len())
+            .find(|&i| !keep[i])
+            .unwrap_or(lines.len());
+        let run = &lines[idx..end];
+        let old_count = run.iter().filter(|l| l.old.is_some()).count();
+        let new_count = run.iter().filter(|l| l.new.is_some()).count();
+        let old_start = run.iter().find_map(|l| l.old).unwrap_or(last_old);
+        let new_start = run.iter().find_map(|l| l.new).unwrap_or(last_new);
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@",
+            old_start, old_count, new_start, new_count
+        ));
+        if let Some(symbol) = hunk_symbol[idx..end].iter().flatten().next() {
+            text.push_str(&format!(" {}", symbol));
+        }
+        text.push('\n');
+
+        for line in run {
+            if line_count >= max_lines_per_file {
+                text.push_str("\n[Note: Diff output truncated to max lines per file.]\n");
+                return FunctionHunks { text, symbols };
+            }
+            text.push(line.origin);
+            push_diff_line(&mut text, &line.content, max_line_width);
+            if !line.content.ends_with('\n') {
+                text.push('\n');
+            }
+            line_count += 1;
+            last_old = line.old.unwrap_or(last_old);
+            last_new = line.new.unwrap_or(last_new);
+        }
+        idx = end;
+    }
+
+    FunctionHunks { text, symbols }
+}
+
+/// Keep `context` lines either side of `idx`.
+fn mark_window(keep: &mut [bool], idx: usize, context: usize) {
+    let start = idx.saturating_sub(context);
+    let end = (idx + context).min(keep.len() - 1);
+    keep[start..=end].iter_mut().for_each(|k| *k = true);
 }
 
 /// Whether the working tree has tracked changes that are not staged.
@@ -531,7 +1547,7 @@ pub fn stage_tracked_changes(repo: &Repository) -> Result<(), GitError> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use std::fs::{self, File};
     use std::io::Write;
     use std::path::Path;
     use tempfile::TempDir;
@@ -666,11 +1682,11 @@ mod tests {
 
         // Lock file should be in ignored_files (auto-skipped)
         assert_eq!(staged.stats.ignored_files.len(), 1);
-        assert_eq!(staged.stats.ignored_files[0].0, "test.lock");
+        assert_eq!(staged.stats.ignored_files[0].path, "test.lock");
 
         // Regular file should be in file_changes
         assert_eq!(staged.stats.file_changes.len(), 1);
-        assert_eq!(staged.stats.file_changes[0].0, "test.txt");
+        assert_eq!(staged.stats.file_changes[0].path, "test.txt");
 
         // Total stats should include both files
         assert_eq!(staged.stats.files_changed, 2);
@@ -787,10 +1803,13 @@ mod tests {
 
         // Check per-file stats
         assert_eq!(staged.stats.file_changes.len(), 1);
-        let (file, adds, dels) = &staged.stats.file_changes[0];
-        assert_eq!(file, "test.txt");
-        assert_eq!(*adds, 5);
-        assert_eq!(*dels, 0);
+        assert_eq!(
+            staged.stats.file_changes[0],
+            FileStat {
+                kind: ChangeKind::Added,
+                ..FileStat::new("test.txt", 5, 0)
+            }
+        );
     }
 
     #[test]
@@ -808,10 +1827,10 @@ mod tests {
 
         // Check per-file stats - should have 2 insertions (modified, line4) and 1 deletion (line2)
         assert_eq!(staged.stats.file_changes.len(), 1);
-        let (file, adds, dels) = &staged.stats.file_changes[0];
-        assert_eq!(file, "test.txt");
-        assert_eq!(*adds, 2);
-        assert_eq!(*dels, 1);
+        assert_eq!(
+            staged.stats.file_changes[0],
+            FileStat::new("test.txt", 2, 1)
+        );
     }
 
     #[test]
@@ -841,33 +1860,30 @@ mod tests {
             .stats
             .file_changes
             .iter()
-            .find(|(f, _, _)| f == "file1.txt");
+            .find(|f| f.path == "file1.txt");
         let file2_stats = staged
             .stats
             .file_changes
             .iter()
-            .find(|(f, _, _)| f == "file2.txt");
+            .find(|f| f.path == "file2.txt");
         let file3_stats = staged
             .stats
             .file_changes
             .iter()
-            .find(|(f, _, _)| f == "file3.txt");
+            .find(|f| f.path == "file3.txt");
 
         assert!(file1_stats.is_some());
         assert!(file2_stats.is_some());
         assert!(file3_stats.is_some());
 
-        let (_, adds1, dels1) = file1_stats.unwrap();
-        assert_eq!(*adds1, 2);
-        assert_eq!(*dels1, 1);
+        let file1 = file1_stats.unwrap();
+        assert_eq!((file1.insertions, file1.deletions), (2, 1));
 
-        let (_, adds2, dels2) = file2_stats.unwrap();
-        assert_eq!(*adds2, 0);
-        assert_eq!(*dels2, 1);
+        let file2 = file2_stats.unwrap();
+        assert_eq!((file2.insertions, file2.deletions), (0, 1));
 
-        let (_, adds3, dels3) = file3_stats.unwrap();
-        assert_eq!(*adds3, 3);
-        assert_eq!(*dels3, 0);
+        let file3 = file3_stats.unwrap();
+        assert_eq!((file3.insertions, file3.deletions), (3, 0));
     }
 
     #[test]
@@ -887,8 +1903,8 @@ mod tests {
         let staged = get_staged_changes(&repo, 0, 100, 300, 0, &[]).unwrap();
 
         // Sum up per-file stats
-        let total_adds: usize = staged.stats.file_changes.iter().map(|(_, a, _)| a).sum();
-        let total_dels: usize = staged.stats.file_changes.iter().map(|(_, _, d)| d).sum();
+        let total_adds: usize = staged.stats.file_changes.iter().map(|f| f.insertions).sum();
+        let total_dels: usize = staged.stats.file_changes.iter().map(|f| f.deletions).sum();
 
         // Verify they match overall stats
         assert_eq!(total_adds, staged.stats.insertions);
@@ -934,11 +1950,11 @@ mod tests {
 
         // Regular file should be in file_changes
         assert_eq!(staged.stats.file_changes.len(), 1);
-        assert_eq!(staged.stats.file_changes[0].0, "regular.txt");
+        assert_eq!(staged.stats.file_changes[0].path, "regular.txt");
 
         // SQL file should be in ignored_files
         assert_eq!(staged.stats.ignored_files.len(), 1);
-        assert_eq!(staged.stats.ignored_files[0].0, "ignored.sql");
+        assert_eq!(staged.stats.ignored_files[0].path, "ignored.sql");
 
         // Total stats should include BOTH files (ignored files are only skipped for analysis)
         assert_eq!(staged.stats.files_changed, 2);
@@ -968,11 +1984,11 @@ mod tests {
 
         // Small file should be in file_changes
         assert_eq!(staged.stats.file_changes.len(), 1);
-        assert_eq!(staged.stats.file_changes[0].0, "small.txt");
+        assert_eq!(staged.stats.file_changes[0].path, "small.txt");
 
         // Large file should be in skipped_files
         assert_eq!(staged.stats.skipped_files.len(), 1);
-        assert_eq!(staged.stats.skipped_files[0].0, "large.txt");
+        assert_eq!(staged.stats.skipped_files[0].path, "large.txt");
 
         // Total stats should include BOTH files (skipped files are only skipped for analysis)
         assert_eq!(staged.stats.files_changed, 2);
@@ -1001,4 +2017,465 @@ mod tests {
         assert_eq!(staged.stats.file_changes.len(), 1);
         assert_eq!(staged.stats.skipped_files.len(), 0);
     }
+
+    static DEFAULT_SKIP: SkipConfig = SkipConfig {
+        builtin: true,
+        disable: Vec::new(),
+        rules: Vec::new(),
+    };
+
+    fn patch_settings(cmtignore: &[String]) -> DiffSettings<'static> {
+        DiffSettings {
+            context_lines: 3,
+            max_lines_per_file: 100,
+            max_line_width: 300,
+            max_file_lines: 1000,
+            rename_threshold: 50,
+            function_context: false,
+            max_diff_tokens: 0,
+            full_text: false,
+            skip: &DEFAULT_SKIP,
+            cmtignore: Box::leak(Box::new(Cmtignore::from_patterns(cmtignore))),
+        }
+    }
+
+    #[test]
+    fn test_patch_changes_from_git_diff() {
+        let patch = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+ fn main() {}
+-fn old() {}
++fn new() {}
+diff --git a/generated/api.rs b/generated/api.rs
+new file mode 100644
+--- /dev/null
++++ b/generated/api.rs
+@@ -0,0 +1 @@
++pub struct Api;
+";
+        let ignore = vec!["generated/*".to_string()];
+        let changes = get_patch_changes(patch.as_bytes(), &patch_settings(&ignore)).unwrap();
+
+        assert_eq!(changes.stats.files_changed, 2);
+        assert_eq!(changes.stats.insertions, 2);
+        assert_eq!(changes.stats.deletions, 1);
+        assert_eq!(changes.stats.file_changes[0].path, "src/lib.rs");
+        assert_eq!(changes.stats.ignored_files[0].path, "generated/api.rs");
+        assert!(!changes.stats.has_unstaged);
+        assert!(changes.diff_text.contains("+fn new() {}"));
+        assert!(!changes.diff_text.contains("pub struct Api"));
+    }
+
+    #[test]
+    fn test_patch_changes_from_plain_unified_diff() {
+        // svn-style preamble, timestamps, and a removed line that looks like a header
+        let patch = "\
+Index: notes.txt
+===================================================================
+--- notes.txt\t2024-01-01 10:00:00.000000000 +0000
++++ notes.txt\t2024-01-02 10:00:00.000000000 +0000
+@@ -1,3 +1,2 @@
+ keep
+--- separator
+-gone
++added
+";
+        let changes = get_patch_changes(patch.as_bytes(), &patch_settings(&[])).unwrap();
+        assert_eq!(changes.stats.files_changed, 1);
+        assert_eq!(
+            changes.stats.file_changes[0],
+            FileStat::new("notes.txt", 1, 2)
+        );
+        assert!(changes.diff_text.contains("--- separator"));
+    }
+
+    #[test]
+    fn test_patch_changes_rejects_empty_input() {
+        assert!(get_patch_changes(b"", &patch_settings(&[])).is_err());
+        assert!(get_patch_changes(b"just some text\n", &patch_settings(&[])).is_err());
+    }
+
+    #[test]
+    fn test_changes_unstaged_vs_index() {
+        let (temp_dir, repo) = setup_test_repo();
+        create_and_stage_file(&repo, "a.txt", "one");
+        commit_all(&repo, "Initial commit");
+        create_and_stage_file(&repo, "staged.txt", "staged");
+        fs::write(temp_dir.path().join("a.txt"), "two\n").unwrap();
+
+        let changes = get_changes(&repo, &DiffSource::Unstaged, &patch_settings(&[])).unwrap();
+        assert!(changes.diff_text.contains("+two"));
+        assert!(!changes.diff_text.contains("staged"));
+        assert_eq!(changes.stats.files_changed, 1);
+    }
+
+    #[test]
+    fn test_changes_between_revisions_and_worktree() {
+        let (temp_dir, repo) = setup_test_repo();
+        create_and_stage_file(&repo, "a.txt", "one");
+        commit_all(&repo, "first");
+        create_and_stage_file(&repo, "b.txt", "two");
+        commit_all(&repo, "second");
+        create_and_stage_file(&repo, "c.txt", "three");
+        fs::write(temp_dir.path().join("a.txt"), "edited\n").unwrap();
+
+        let range = DiffSource::Revisions {
+            from: "HEAD~1".to_string(),
+            to: Some("HEAD".to_string()),
+        };
+        assert_eq!(range.to_string(), "HEAD~1..HEAD");
+        let changes = get_changes(&repo, &range, &patch_settings(&[])).unwrap();
+        assert!(changes.diff_text.contains("+two"));
+        assert_eq!(changes.stats.files_changed, 1);
+
+        // Against the working tree: both the staged and the unstaged edits.
+        let worktree = DiffSource::Revisions {
+            from: "HEAD".to_string(),
+            to: None,
+        };
+        let changes = get_changes(&repo, &worktree, &patch_settings(&[])).unwrap();
+        assert!(changes.diff_text.contains("+three"));
+        assert!(changes.diff_text.contains("+edited"));
+        assert_eq!(changes.stats.files_changed, 2);
+
+        let same = DiffSource::Revisions {
+            from: "HEAD".to_string(),
+            to: Some("HEAD".to_string()),
+        };
+        assert!(get_changes(&repo, &same, &patch_settings(&[]))
+            .unwrap()
+            .diff_text
+            .is_empty());
+
+        let unknown = DiffSource::Revisions {
+            from: "no-such-rev".to_string(),
+            to: None,
+        };
+        assert!(get_changes(&repo, &unknown, &patch_settings(&[])).is_err());
+    }
+
+    #[test]
+    fn test_gitattributes_exclude_files() {
+        let (temp_dir, repo) = setup_test_repo();
+        fs::write(
+            temp_dir.path().join(".gitattributes"),
+            "*.pb.go linguist-generated\n\
+             schema.ts linguist-generated=true\n\
+             vendor/** linguist-vendored\n\
+             *.dat -diff\n\
+             *.bin binary\n\
+             notes.md cmt-ignore\n\
+             keep.ts linguist-generated=false\n",
+        )
+        .unwrap();
+        fs::create_dir(temp_dir.path().join("vendor")).unwrap();
+        for (name, content) in [
+            ("api.pb.go", "package api"),
+            ("schema.ts", "export type Q = {}"),
+            ("vendor/lib.js", "vendored()"),
+            ("blob.dat", "data"),
+            ("image.bin", "bytes"),
+            ("notes.md", "scratch"),
+            ("keep.ts", "export const keep = 1"),
+        ] {
+            create_and_stage_file(&repo, name, content);
+        }
+
+        let staged = get_staged_changes(&repo, 3, 100, 300, 0, &[]).unwrap();
+        let reason = |path: &str| {
+            staged
+                .stats
+                .ignored_files
+                .iter()
+                .find(|f| f.path == path)
+                .and_then(|f| f.reason.clone())
+        };
+        assert_eq!(
+            reason("api.pb.go").as_deref(),
+            Some(".gitattributes: linguist-generated")
+        );
+        assert_eq!(
+            reason("schema.ts").as_deref(),
+            Some(".gitattributes: linguist-generated")
+        );
+        assert_eq!(
+            reason("vendor/lib.js").as_deref(),
+            Some(".gitattributes: linguist-vendored")
+        );
+        assert_eq!(reason("blob.dat").as_deref(), Some(".gitattributes: -diff"));
+        assert_eq!(
+            reason("image.bin").as_deref(),
+            Some(".gitattributes: binary")
+        );
+        assert_eq!(
+            reason("notes.md").as_deref(),
+            Some(".gitattributes: cmt-ignore")
+        );
+
+        let included: Vec<&str> = staged
+            .stats
+            .file_changes
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(included, ["keep.ts"]);
+        assert!(!staged.diff_text.contains("package api"));
+        assert!(staged.diff_text.contains("export const keep"));
+    }
+
+    #[test]
+    fn test_lockfile_bumps_become_dependency_changes() {
+        let (_temp_dir, repo) = setup_test_repo();
+        let lock = |serde: &str| {
+            format!(
+                "version = 3\n\n[[package]]\nname = \"cmt\"\nversion = \"0.5.16\"\n\n\
+                 [[package]]\nname = \"serde\"\nversion = \"{}\"\n\
+                 source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                serde
+            )
+        };
+        create_and_stage_file(&repo, "Cargo.lock", &lock("1.0.227"));
+        commit_all(&repo, "Initial commit");
+        create_and_stage_file(&repo, "Cargo.lock", &lock("1.0.228"));
+
+        // No context lines requested: the package names still have to be found.
+        let staged = get_staged_changes(&repo, 0, 100, 300, 0, &[]).unwrap();
+        assert!(staged.diff_text.is_empty());
+        assert_eq!(staged.stats.ignored_files.len(), 1);
+        assert_eq!(staged.stats.ignored_files[0].kind, ChangeKind::Modified);
+        assert_eq!(
+            staged.stats.dependency_changes,
+            [DependencyChange::Bumped {
+                name: "serde".to_string(),
+                from: "1.0.227".to_string(),
+                to: "1.0.228".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_renames_copies_and_binaries_carry_change_kind() {
+        let (temp_dir, repo) = setup_test_repo();
+        let body: String = (0..20).map(|i| format!("fn f{}() {{}}\n", i)).collect();
+        create_and_stage_file(&repo, "old.rs", &body);
+        create_and_stage_file(&repo, "blob.dat", "v1");
+        commit_all(&repo, "Initial commit");
+
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("old.rs")).unwrap();
+        index.write().unwrap();
+        fs::remove_file(temp_dir.path().join("old.rs")).unwrap();
+        create_and_stage_file(&repo, "new.rs", &body);
+        fs::write(temp_dir.path().join("blob.dat"), b"\0\x01\x02binary").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("blob.dat")).unwrap();
+        index.write().unwrap();
+
+        let staged = get_staged_changes(&repo, 3, 100, 300, 0, &[]).unwrap();
+        let stat = |path: &str| {
+            staged
+                .stats
+                .file_changes
+                .iter()
+                .chain(&staged.stats.ignored_files)
+                .find(|f| f.path == path)
+                .cloned()
+                .unwrap()
+        };
+        let renamed = stat("new.rs");
+        assert_eq!(renamed.kind, ChangeKind::Renamed);
+        assert_eq!(renamed.old_path.as_deref(), Some("old.rs"));
+        assert_eq!(renamed.describe_kind(), "renamed from old.rs");
+        assert_eq!((renamed.insertions, renamed.deletions), (0, 0));
+        assert_eq!(stat("blob.dat").kind, ChangeKind::Binary);
+        assert_eq!(
+            stat("blob.dat").reason.as_deref(),
+            Some("skip rule binary: binary content")
+        );
+        assert_eq!(staged.stats.files_changed, 2);
+
+        // A zero threshold turns detection off: a delete plus an add.
+        let settings = DiffSettings {
+            rename_threshold: 0,
+            ..patch_settings(&[])
+        };
+        let changes = get_changes(&repo, &DiffSource::Staged, &settings).unwrap();
+        assert_eq!(changes.stats.files_changed, 3);
+        assert!(changes
+            .stats
+            .file_changes
+            .iter()
+            .any(|f| f.path == "old.rs" && f.kind == ChangeKind::Deleted));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mode_change_is_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (temp_dir, repo) = setup_test_repo();
+        create_and_stage_file(&repo, "run.sh", "echo hi");
+        commit_all(&repo, "Initial commit");
+
+        let path = temp_dir.path().join("run.sh");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("run.sh")).unwrap();
+        index.write().unwrap();
+
+        let staged = get_staged_changes(&repo, 3, 100, 300, 0, &[]).unwrap();
+        let stat = &staged.stats.file_changes[0];
+        assert_eq!(stat.kind, ChangeKind::ModeChange);
+        assert_eq!(stat.describe_kind(), "mode change 100644 -> 100755");
+    }
+
+    #[test]
+    fn test_function_context_expands_to_enclosing_function() {
+        let (_temp_dir, repo) = setup_test_repo();
+        let source = |value: &str| {
+            format!(
+                "fn first() {{\n    let a = 1;\n    a\n}}\n\n\
+                 fn second() -> u32 {{\n    let setup = 2;\n    let other = 3;\n    \
+                 setup + other + {}\n}}\n",
+                value
+            )
+        };
+        create_and_stage_file(&repo, "lib.rs", &source("1"));
+        create_and_stage_file(&repo, "notes.txt", "one\ntwo\nthree");
+        commit_all(&repo, "Initial commit");
+        create_and_stage_file(&repo, "lib.rs", &source("4"));
+        create_and_stage_file(&repo, "notes.txt", "one\n2\nthree");
+
+        let settings = DiffSettings {
+            context_lines: 0,
+            function_context: true,
+            ..patch_settings(&[])
+        };
+        let changes = get_changes(&repo, &DiffSource::Staged, &settings).unwrap();
+        let diff = &changes.diff_text;
+
+        // The whole of second() is shown, nothing of first().
+        assert!(diff.contains("@@ -6,5 +6,5 @@ second\n fn second() -> u32 {\n"));
+        assert!(diff.contains("-    setup + other + 1\n+    setup + other + 4\n }\n"));
+        assert!(!diff.contains("fn first"));
+        // Unsupported languages keep plain line context.
+        assert!(diff.contains("-two\n+2\n"));
+        assert!(!diff.contains("\n one\n"));
+
+        let symbols: Vec<(&str, &[String])> = changes
+            .stats
+            .file_changes
+            .iter()
+            .map(|f| (f.path.as_str(), f.symbols.as_slice()))
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("lib.rs", &["second".to_string()][..]),
+                ("notes.txt", &[][..])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_submodule_bump_reads_upstream_commits() {
+        let (temp_dir, repo) = setup_test_repo();
+        let sub = Repository::init(temp_dir.path().join("vendor")).unwrap();
+        let mut sub_config = sub.config().unwrap();
+        sub_config.set_str("user.name", "Test User").unwrap();
+        sub_config
+            .set_str("user.email", "test@example.com")
+            .unwrap();
+        commit_all(&sub, "Initial import");
+        let old = sub.head().unwrap().target().unwrap();
+        commit_all(&sub, "Add retry with backoff");
+        let new = sub.head().unwrap().target().unwrap();
+
+        let stage_gitlink = |oid: Oid| {
+            let mut index = repo.index().unwrap();
+            index
+                .add(&git2::IndexEntry {
+                    ctime: git2::IndexTime::new(0, 0),
+                    mtime: git2::IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode: 0o160000,
+                    uid: 0,
+                    gid: 0,
+                    file_size: 0,
+                    id: oid,
+                    flags: 0,
+                    flags_extended: 0,
+                    path: b"vendor".to_vec(),
+                })
+                .unwrap();
+            index.write().unwrap();
+        };
+        stage_gitlink(old);
+        commit_all(&repo, "Add vendor");
+        stage_gitlink(new);
+
+        let changes = get_changes(&repo, &DiffSource::Staged, &patch_settings(&[])).unwrap();
+        assert_eq!(changes.stats.file_changes[0].kind, ChangeKind::Submodule);
+        let update = &changes.stats.submodule_updates[0];
+        assert_eq!(update.path, "vendor");
+        assert_eq!(update.commit_count, 1);
+        assert_eq!(update.commits, ["Add retry with backoff"]);
+    }
+
+    #[test]
+    fn test_token_budget_keeps_source_and_reports_cuts() {
+        let (_temp_dir, repo) = setup_test_repo();
+        create_and_stage_file(&repo, "lib.rs", "pub fn answer() -> u32 {\n    42\n}\n");
+        create_and_stage_file(&repo, "view.snap", &"{\"value\": 1}\n".repeat(200));
+
+        let unlimited = get_changes(&repo, &DiffSource::Staged, &patch_settings(&[])).unwrap();
+        assert!(unlimited.stats.budget.is_none());
+
+        let settings = DiffSettings {
+            max_diff_tokens: 200,
+            ..patch_settings(&[])
+        };
+        let changes = get_changes(&repo, &DiffSource::Staged, &settings).unwrap();
+        assert!(changes.diff_text.contains("+    42"));
+        assert!(!changes.diff_text.contains("value"));
+        let budget = changes.stats.budget.unwrap();
+        assert_eq!(budget.max_tokens, 200);
+        assert_eq!(budget.cut_files.len(), 1);
+        assert_eq!(budget.cut_files[0].path, "view.snap");
+        assert!(budget.cut_files[0].is_dropped());
+        // Still counted in the stats; only the diff text is cut
+        assert_eq!(changes.stats.files_changed, 2);
+    }
+
+    #[test]
+    fn test_ignored_files_record_reason() {
+        let (_temp_dir, repo) = setup_test_repo();
+        create_and_stage_file(&repo, "Cargo.lock", "lock");
+        create_and_stage_file(&repo, "query.sql", "select 1");
+        create_and_stage_file(&repo, "big.txt", "1\n2\n3\n4");
+        create_and_stage_file(&repo, "small.txt", "1");
+
+        let patterns = vec!["*.sql".to_string()];
+        let staged = get_staged_changes(&repo, 3, 100, 300, 2, &patterns).unwrap();
+        let reasons: Vec<(&str, Option<&str>)> = staged
+            .stats
+            .ignored_files
+            .iter()
+            .chain(&staged.stats.skipped_files)
+            .map(|f| (f.path.as_str(), f.reason.as_deref()))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                ("Cargo.lock", Some("skip rule lockfile: *.lock")),
+                ("query.sql", Some(".cmtignore: *.sql")),
+                ("big.txt", Some("over 2 lines")),
+            ]
+        );
+    }
 }
@@ -0,0 +1,91 @@
+//! Deterministic issue/ticket references extracted from the branch name.
+//!
+//! Branch names like `feat/PROJ-123-login` or `fix/issue-42` already carry the
+//! ticket. Rather than hoping the model infers (or invents) it, configurable
+//! regexes pull the IDs out and fill `CommitTemplate.issues` directly.
+
+use regex::Regex;
+
+/// Extract ticket IDs from `branch` using `patterns`, in order of appearance.
+///
+/// Each pattern's first capture group is used as the ID when present (so
+/// `issue-(\d+)` yields `42`), otherwise the whole match (`[A-Z]+-\d+` yields
+/// `PROJ-123`). Duplicates are dropped. Invalid patterns are reported rather
+/// than silently ignored, since a typo would otherwise disable the feature.
+pub fn extract_issue_ids(branch: &str, patterns: &[String]) -> Result<Vec<String>, regex::Error> {
+    let mut found: Vec<(usize, String)> = Vec::new();
+
+    for pattern in patterns {
+        let re = Regex::new(pattern)?;
+        for caps in re.captures_iter(branch) {
+            let m = caps.get(1).or_else(|| caps.get(0));
+            if let Some(m) = m.filter(|m| !m.as_str().is_empty()) {
+                found.push((m.start(), m.as_str().to_string()));
+            }
+        }
+    }
+
+    found.sort_by_key(|(pos, _)| *pos);
+    let mut ids: Vec<String> = Vec::new();
+    for (_, id) in found {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Render the issue footer: one line per comma-separated reference in
+/// `issues`, with `{id}` in `format` replaced by the reference
+/// (e.g. `Refs: #{id}` -> `Refs: #123`, `Closes {id}` -> `Closes PROJ-9`).
+pub fn format_footer(issues: &str, format: &str) -> String {
+    issues
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| format.replace("{id}", id))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(p: &[&str]) -> Vec<String> {
+        p.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_extracts_whole_match_and_capture_group() {
+        let ids = extract_issue_ids(
+            "feat/PROJ-123-issue-42-login",
+            &patterns(&[r"[A-Z]+-\d+", r"issue-(\d+)"]),
+        )
+        .unwrap();
+        assert_eq!(ids, vec!["PROJ-123", "42"]);
+    }
+
+    #[test]
+    fn test_dedupes_and_handles_no_match() {
+        let ids = extract_issue_ids("PROJ-9/PROJ-9-again", &patterns(&[r"[A-Z]+-\d+"])).unwrap();
+        assert_eq!(ids, vec!["PROJ-9"]);
+
+        let ids = extract_issue_ids("main", &patterns(&[r"[A-Z]+-\d+"])).unwrap();
+        assert!(ids.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_an_error() {
+        assert!(extract_issue_ids("x", &patterns(&["(unclosed"])).is_err());
+    }
+
+    #[test]
+    fn test_format_footer() {
+        assert_eq!(format_footer("123", "Refs: #{id}"), "Refs: #123");
+        assert_eq!(
+            format_footer("PROJ-9, PROJ-10", "Closes {id}"),
+            "Closes PROJ-9\nCloses PROJ-10"
+        );
+    }
+}
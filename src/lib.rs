@@ -1,36 +1,68 @@
-pub use crate::config::cli::Args;
+pub use crate::config::cli::{Args, Command, IgnoreCommand};
 pub use crate::config::Config;
 pub use crate::git::{
-    get_current_branch, get_readme_excerpt, get_recent_commits, get_staged_changes,
-    has_unstaged_changes, stage_tracked_changes, DiffStats, StagedChanges,
+    get_branch_commits, get_changes, get_current_branch, get_patch_changes, get_readme_excerpt,
+    get_recent_commits, get_staged_changes, has_unstaged_changes, stage_tracked_changes,
+    BranchCommit, BranchRange, ChangeKind, DiffSettings, DiffSource, DiffStats, FileStat,
+    StagedChanges,
 };
 
 mod ai;
+mod budget;
 mod cmtignore;
 mod commit;
 mod config;
+mod engine;
+mod fixup;
 mod git;
+mod issues;
+mod lockfile;
+mod operation;
+mod outline;
+mod output;
 pub mod pricing;
 mod progress;
 mod prompts;
 mod redact;
+mod serve;
+mod skip;
+mod submodule;
 mod templates;
+mod trailers;
 
+pub use budget::{estimate_tokens, BudgetReport, CutFile};
 pub use cmtignore::{append_to_cmtignore, load_cmtignore};
-pub use redact::redact_secrets;
+pub use lockfile::{DependencyChange, Lockfile};
+pub use prompts::load_project_prompts;
+pub use redact::{
+    redact_secrets, Finding, PiiConfig, PlaceholderMap, PlaceholderRule, Placeholders,
+    Pseudonymizer, Redactor, SecretRule, SecretsConfig,
+};
+pub use serve::Server;
+pub use skip::{SkipConfig, SkipRule};
+pub use submodule::SubmoduleUpdate;
+pub use trailers::{append_trailers, resolve_trailers, squash_co_authors, Trailer};
+
+pub use fixup::{find_fixup_target, resolve_fixup_target, FixupTarget};
+pub use operation::{Operation, OperationKind};
+pub use output::{CommitReport, ErrorReport, FixupReport, JsonReport, OutputFormat, UsageReport};
+
+pub use engine::{CmtEngine, CmtEngineBuilder, CmtError, DiffContext};
 
-pub use commit::{create_commit, CommitError, CommitOptions, CommitResult};
+pub use commit::{create_commit, CommitError, CommitOptions, CommitResult, SignMode};
 
 pub use pricing::PricingCache;
 pub use progress::Spinner;
 
-use templates::{CommitTemplate, TemplateManager};
+use templates::{CommitTemplate, TemplateError, TemplateManager};
 
 /// Result of commit message generation
 #[derive(Debug)]
 pub struct GenerateResult {
     /// The rendered commit message
     pub message: String,
+    /// The structured data the message was rendered from
+    pub template: CommitTemplate,
     /// Input tokens used (if available from provider)
     pub input_tokens: Option<u64>,
     /// Output tokens used (if available from provider)
@@ -93,113 +125,105 @@ fn validate_commit_data(mut data: CommitTemplate) -> CommitTemplate {
     data
 }
 
-pub async fn generate_commit_message(
+/// The fully assembled prompt pair sent to the model.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub system: String,
+    pub user: String,
+    /// Tokens standing in for `[placeholders]` values in the prompt, to be
+    /// restored in the model's answer
+    pub placeholders: PlaceholderMap,
+}
+
+/// Assemble the system and user prompts without calling the model.
+///
+/// Project overrides (`.cmt.toml` keys or `.cmt/prompts/*.txt`, already folded
+/// into `config`) replace or extend the built-in prompts; both are rendered as
+/// Handlebars templates with the diff and context as variables.
+pub fn build_prompt(
     config: &Config,
     git_diff: &str,
     recent_commits: &str,
     branch_name: Option<&str>,
     readme_excerpt: Option<&str>,
-    template_manager: &TemplateManager,
-) -> Result<GenerateResult, Box<dyn std::error::Error>> {
-    let template_name = config
-        .template
-        .clone()
-        .unwrap_or_else(|| crate::config::defaults::DEFAULT_TEMPLATE.to_string());
-
-    // Get provider name
-    let provider_name = &config.provider;
-
-    // Check if the provider is available (has API key)
-    ai::check_available(provider_name)?;
-
-    // Get the model name, defaulting to the provider's default model
-    let model = config
-        .model
-        .clone()
-        .unwrap_or_else(|| ai::default_model(provider_name).to_string());
+) -> Result<Prompt, TemplateError> {
+    // Branch names like main/master/detached carry no intent worth sending.
+    let branch = branch_name.filter(|branch| {
+        *branch != "main" && *branch != "master" && !branch.starts_with("detached@")
+    });
+    let recent =
+        (config.include_recent_commits && !recent_commits.is_empty()).then_some(recent_commits);
+
+    // Context is added around the templates unless they place it themselves.
+    let user_template = prompts::user_template(config);
+    let system_template = prompts::system_template(config);
+    let placed = |name: &str| {
+        prompts::uses_var(&user_template, name) || prompts::uses_var(&system_template, name)
+    };
 
-    // Build the prompt for the AI provider
-    let mut prompt = String::new();
+    let mut user = String::new();
 
     // Include README excerpt for project context
-    if let Some(readme) = readme_excerpt {
-        prompt.push_str("Project README:\n");
-        prompt.push_str(readme);
-        prompt.push_str("\n\n");
+    if let Some(readme) = readme_excerpt.filter(|_| !placed("readme")) {
+        user.push_str("Project README:\n");
+        user.push_str(readme);
+        user.push_str("\n\n");
     }
 
     // Include branch name for context (often contains feature/ticket info)
-    if let Some(branch) = branch_name {
-        if branch != "main" && branch != "master" && !branch.starts_with("detached@") {
-            prompt.push_str(&format!("Branch: {}\n", branch));
-        }
+    if let Some(branch) = branch.filter(|_| !placed("branch")) {
+        user.push_str(&format!("Branch: {}\n", branch));
     }
 
-    if config.include_recent_commits && !recent_commits.is_empty() {
-        prompt.push_str("\nRecent commits for context:\n");
-        prompt.push_str(recent_commits);
+    if let Some(recent) = recent.filter(|_| !placed("recent_commits")) {
+        user.push_str("\nRecent commits for context:\n");
+        user.push_str(recent);
     }
 
-    prompt.push_str(&prompts::user_prompt(git_diff));
+    let vars = prompts::PromptVars {
+        changes: git_diff,
+        branch,
+        recent_commits: recent,
+        readme: readme_excerpt,
+        hint: config.hint.as_deref(),
+    };
+    user.push_str(&prompts::render(&user_template, &vars)?);
 
     // Build the system prompt
-    let mut system_prompt = prompts::system_prompt();
-    if let Some(hint) = &config.hint {
-        system_prompt = format!("{}\n\nAdditional context: {}", system_prompt, hint);
+    let mut system = prompts::render(&system_template, &vars)?;
+    if let Some(hint) = config.hint.as_ref().filter(|_| !placed("hint")) {
+        system = format!("{}\n\nAdditional context: {}", system, hint);
     }
 
-    // Generate the commit message
-    let temperature = config.temperature.unwrap_or(ai::DEFAULT_TEMPERATURE);
-    // Provider-specific thinking quirks are normalized inside complete_structured.
-    let thinking_level = Some(ai::ThinkingLevel::parse(&config.thinking));
-
-    // Try to complete the prompt with structured output
-    let completion = match ai::complete_structured(
-        provider_name,
-        &model,
-        temperature,
-        &system_prompt,
-        &prompt,
-        thinking_level,
-        config.timeout_secs,
-    )
-    .await
-    {
-        Ok(result) => result,
-        Err(err) => {
-            // Check for invalid model error
-            if let Some(ai::AiError::InvalidModel { model, .. }) = err.downcast_ref::<ai::AiError>()
-            {
-                return Err(format!(
-                    "Invalid model: {} for provider: {}\nCheck the provider's documentation for available models.",
-                    model,
-                    provider_name
-                )
-                .into());
-            }
-            return Err(err);
-        }
-    };
-
-    // Validate and fix the commit data
-    let commit_data = validate_commit_data(completion.template);
-
-    // Render the template
-    let rendered = template_manager.render(&template_name, &commit_data)?;
-
-    // Extract token usage if available
-    let (input_tokens, output_tokens) = match completion.usage {
-        Some(usage) => (Some(usage.input_tokens), Some(usage.output_tokens)),
-        None => (None, None),
-    };
-
-    Ok(GenerateResult {
-        message: rendered,
-        input_tokens,
-        output_tokens,
+    Ok(Prompt {
+        system,
+        user,
+        placeholders: PlaceholderMap::default(),
     })
 }
 
+/// Generate a rendered commit message for `git_diff`.
+///
+/// Kept for compatibility; [`CmtEngine`] wraps the same pipeline with typed
+/// errors and owns the config, templates and repository.
+pub async fn generate_commit_message(
+    config: &Config,
+    git_diff: &str,
+    recent_commits: &str,
+    branch_name: Option<&str>,
+    readme_excerpt: Option<&str>,
+    template_manager: &TemplateManager,
+) -> Result<GenerateResult, Box<dyn std::error::Error>> {
+    let prompt = build_prompt(
+        config,
+        git_diff,
+        recent_commits,
+        branch_name,
+        readme_excerpt,
+    )?;
+    Ok(engine::generate_message(config, &prompt, branch_name, template_manager).await?)
+}
+
 // Re-export the config module for external use
 pub mod config_mod {
     pub use crate::config::file;
@@ -229,6 +253,23 @@ mod tests {
     use crate::config::cli::Args;
     use std::env;
 
+    #[test]
+    fn test_build_prompt_skips_context_the_templates_place() {
+        let config = Config {
+            user_prompt: Some("About: {{readme}}\n{{changes}}".to_string()),
+            system_prompt: Some("House rules. {{#if hint}}Note: {{hint}}{{/if}}".to_string()),
+            hint: Some("keep it short".to_string()),
+            ..Config::default()
+        };
+        let prompt = build_prompt(&config, "+x", "", Some("feature/login"), Some("A CLI")).unwrap();
+
+        assert_eq!(prompt.user.matches("A CLI").count(), 1, "{}", prompt.user);
+        assert!(!prompt.user.contains("Project README:"));
+        // Not in the template, so still added automatically
+        assert!(prompt.user.starts_with("Branch: feature/login\n"));
+        assert_eq!(prompt.system, "House rules. Note: keep it short");
+    }
+
     #[tokio::test]
     async fn test_unsupported_provider() {
         // Create args with an unsupported provider
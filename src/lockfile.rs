@@ -0,0 +1,370 @@
+//! Dependency changes read from lockfile diffs.
+//!
+//! Lockfiles are kept out of the diff sent to the model, so a bare "Cargo.lock
+//! changed" is all it would otherwise learn. Parsing the old and new sides of
+//! `Cargo.lock`, `package-lock.json`, `pnpm-lock.yaml` and `yarn.lock` into
+//! `(package, version)` pairs turns the change into "serde 1.0.227 → 1.0.228",
+//! which is enough for an accurate `build: bump serde to 1.0.228`.
+//!
+//! The parsers are line-based and tolerant: they work on whole files or on the
+//! context-plus-changed lines of a diff hunk, and skip anything they don't
+//! recognize.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+/// A package added, removed or moved to another version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum DependencyChange {
+    Added {
+        name: String,
+        version: String,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+    Bumped {
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for DependencyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependencyChange::Added { name, version } => write!(f, "{} {} added", name, version),
+            DependencyChange::Removed { name, version } => {
+                write!(f, "{} {} removed", name, version)
+            }
+            DependencyChange::Bumped { name, from, to } => {
+                write!(f, "{} bumped from {} to {}", name, from, to)
+            }
+        }
+    }
+}
+
+/// Lockfile formats cmt understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lockfile {
+    Cargo,
+    Npm,
+    Pnpm,
+    Yarn,
+}
+
+impl Lockfile {
+    /// The format of `path`, by file name.
+    pub fn detect(path: &Path) -> Option<Self> {
+        match path.file_name()?.to_str()?.to_lowercase().as_str() {
+            "cargo.lock" => Some(Lockfile::Cargo),
+            "package-lock.json" | "npm-shrinkwrap.json" => Some(Lockfile::Npm),
+            "pnpm-lock.yaml" => Some(Lockfile::Pnpm),
+            "yarn.lock" => Some(Lockfile::Yarn),
+            _ => None,
+        }
+    }
+
+    /// `(package, version)` pairs found in `text`.
+    pub fn packages(self, text: &str) -> Vec<(String, String)> {
+        match self {
+            Lockfile::Cargo => parse_cargo(text),
+            Lockfile::Npm => parse_npm(text),
+            Lockfile::Pnpm => parse_pnpm(text),
+            Lockfile::Yarn => parse_yarn(text),
+        }
+    }
+
+    /// Dependency changes between the old and new contents of a lockfile.
+    pub fn changes(self, old: &str, new: &str) -> Vec<DependencyChange> {
+        self.hunk_changes([(old, new)])
+    }
+
+    /// Dependency changes across a diff, given each hunk's old and new side.
+    /// Hunks are parsed separately, so a package cut off at the end of one
+    /// hunk is never paired with a version from the next.
+    pub fn hunk_changes<'a>(
+        self,
+        hunks: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Vec<DependencyChange> {
+        let (mut old, mut new) = (Vec::new(), Vec::new());
+        for (old_side, new_side) in hunks {
+            old.extend(self.packages(old_side));
+            new.extend(self.packages(new_side));
+        }
+        diff_packages(&old, &new)
+    }
+}
+
+/// The quoted value after `key = ` on a TOML line.
+fn toml_string<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line
+        .trim()
+        .strip_prefix(key)?
+        .trim_start()
+        .strip_prefix('=')?;
+    Some(rest.trim().trim_matches('"'))
+}
+
+fn parse_cargo(text: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    let mut name: Option<&str> = None;
+    for line in text.lines() {
+        if line.trim() == "[[package]]" {
+            name = None;
+        } else if let Some(n) = toml_string(line, "name") {
+            name = Some(n);
+        } else if let (Some(n), Some(version)) = (name, toml_string(line, "version")) {
+            packages.push((n.to_string(), version.to_string()));
+            name = None;
+        }
+    }
+    packages
+}
+
+/// `"node_modules/a/node_modules/@scope/b": {` → `@scope/b`
+fn npm_package_key(line: &str) -> Option<&str> {
+    let key = line
+        .trim()
+        .strip_suffix('{')?
+        .trim_end()
+        .strip_suffix(':')?;
+    let key = key.trim().trim_matches('"');
+    let idx = key.rfind("node_modules/")?;
+    Some(&key[idx + "node_modules/".len()..])
+}
+
+fn parse_npm(text: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    let mut name: Option<&str> = None;
+    for line in text.lines() {
+        if let Some(key) = npm_package_key(line) {
+            name = Some(key);
+        } else if let Some(n) = name {
+            let trimmed = line.trim();
+            if let Some(rest) = trimmed.strip_prefix("\"version\":") {
+                let version = rest.trim().trim_end_matches(',').trim_matches('"');
+                packages.push((n.to_string(), version.to_string()));
+                name = None;
+            } else if trimmed.starts_with('}') {
+                name = None;
+            }
+        }
+    }
+    packages
+}
+
+/// Split `name@version`, keeping a leading `@scope/`.
+fn split_at_version(spec: &str) -> Option<(&str, &str)> {
+    let at = spec.get(1..)?.find('@')? + 1;
+    let (name, version) = (&spec[..at], &spec[at + 1..]);
+    (!name.is_empty() && !version.is_empty()).then_some((name, version))
+}
+
+fn parse_pnpm(text: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    for line in text.lines() {
+        // Package keys are indented two spaces under `packages:` (or
+        // `snapshots:`): `  /name@1.2.3:` (v6) or `  name@1.2.3:` (v9),
+        // optionally quoted and with a `(peer@x)` suffix.
+        let Some(key) = line.strip_prefix("  ") else {
+            continue;
+        };
+        if key.starts_with(' ') {
+            continue;
+        }
+        let Some(key) = key.trim_end().strip_suffix(':') else {
+            continue;
+        };
+        let key = key.trim_matches(|c| c == '\'' || c == '"');
+        let key = key.strip_prefix('/').unwrap_or(key);
+        let key = key.split('(').next().unwrap_or(key);
+        if let Some((name, version)) = split_at_version(key) {
+            if version.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+                packages.push((name.to_string(), version.to_string()));
+            }
+        }
+    }
+    packages.sort();
+    packages.dedup();
+    packages
+}
+
+fn parse_yarn(text: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    let mut name: Option<String> = None;
+    for line in text.lines() {
+        if !line.starts_with([' ', '#']) && line.trim_end().ends_with(':') {
+            // `"@scope/a@^1.0.0", "@scope/a@^1.1.0":` or `a@^1.0.0:`
+            let first = line.trim_end().trim_end_matches(':');
+            let first = first.split(", ").next().unwrap_or(first).trim_matches('"');
+            name = split_at_version(first).map(|(n, _)| n.to_string());
+        } else if let Some(n) = &name {
+            let trimmed = line.trim();
+            let version = trimmed
+                .strip_prefix("version ")
+                .or_else(|| trimmed.strip_prefix("version: "));
+            if let Some(version) = version {
+                packages.push((n.clone(), version.trim().trim_matches('"').to_string()));
+                name = None;
+            }
+        }
+    }
+    packages
+}
+
+/// Compare two package lists by name: one version swapped for another is a
+/// bump; anything else is reported as added and removed versions.
+fn diff_packages(old: &[(String, String)], new: &[(String, String)]) -> Vec<DependencyChange> {
+    fn by_name(list: &[(String, String)]) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut map: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (name, version) in list {
+            map.entry(name).or_default().insert(version);
+        }
+        map
+    }
+    let (old, new) = (by_name(old), by_name(new));
+    let empty = BTreeSet::new();
+
+    let names: BTreeSet<&str> = old.keys().chain(new.keys()).copied().collect();
+    let mut changes = Vec::new();
+    for name in names {
+        let before = old.get(name).unwrap_or(&empty);
+        let after = new.get(name).unwrap_or(&empty);
+        let removed: Vec<&str> = before.difference(after).copied().collect();
+        let added: Vec<&str> = after.difference(before).copied().collect();
+        match (removed.as_slice(), added.as_slice()) {
+            ([from], [to]) => changes.push(DependencyChange::Bumped {
+                name: name.to_string(),
+                from: from.to_string(),
+                to: to.to_string(),
+            }),
+            _ => {
+                changes.extend(removed.iter().map(|v| DependencyChange::Removed {
+                    name: name.to_string(),
+                    version: v.to_string(),
+                }));
+                changes.extend(added.iter().map(|v| DependencyChange::Added {
+                    name: name.to_string(),
+                    version: v.to_string(),
+                }));
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bumped(name: &str, from: &str, to: &str) -> DependencyChange {
+        DependencyChange::Bumped {
+            name: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cargo_lock() {
+        let old = "[[package]]\nname = \"serde\"\nversion = \"1.0.227\"\nsource = \"registry\"\n\n[[package]]\nname = \"old-dep\"\nversion = \"0.1.0\"\n";
+        let new = "[[package]]\nname = \"serde\"\nversion = \"1.0.228\"\nsource = \"registry\"\n\n[[package]]\nname = \"new-dep\"\nversion = \"2.0.0\"\n";
+        assert_eq!(
+            Lockfile::Cargo.changes(old, new),
+            vec![
+                DependencyChange::Added {
+                    name: "new-dep".to_string(),
+                    version: "2.0.0".to_string()
+                },
+                DependencyChange::Removed {
+                    name: "old-dep".to_string(),
+                    version: "0.1.0".to_string()
+                },
+                bumped("serde", "1.0.227", "1.0.228"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cargo_lock_keeps_duplicate_versions_apart() {
+        let old = "name = \"syn\"\nversion = \"1.0.109\"\nname = \"syn\"\nversion = \"2.0.1\"\n";
+        let new = "name = \"syn\"\nversion = \"1.0.109\"\nname = \"syn\"\nversion = \"2.0.2\"\n";
+        assert_eq!(
+            Lockfile::Cargo.changes(old, new),
+            vec![bumped("syn", "2.0.1", "2.0.2")]
+        );
+    }
+
+    #[test]
+    fn test_package_lock_json() {
+        let old = r#"    "node_modules/lodash": {
+      "version": "4.17.20",
+      "resolved": "https://registry.npmjs.org/lodash/-/lodash-4.17.20.tgz"
+    },
+    "node_modules/a/node_modules/@types/node": {
+      "version": "20.1.0",
+"#;
+        let new = old
+            .replace("4.17.20", "4.17.21")
+            .replace("20.1.0", "20.2.0");
+        assert_eq!(
+            Lockfile::Npm.changes(old, &new),
+            vec![
+                bumped("@types/node", "20.1.0", "20.2.0"),
+                bumped("lodash", "4.17.20", "4.17.21"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pnpm_lock() {
+        let old = "packages:\n\n  /react@18.2.0:\n    resolution: {integrity: x}\n\n  '@babel/core@7.22.0(supports-color@5.5.0)':\n    dependencies:\n";
+        let new = "packages:\n\n  /react@18.3.1:\n    resolution: {integrity: y}\n\n  '@babel/core@7.24.0(supports-color@5.5.0)':\n    dependencies:\n";
+        assert_eq!(
+            Lockfile::Pnpm.changes(old, new),
+            vec![
+                bumped("@babel/core", "7.22.0", "7.24.0"),
+                bumped("react", "18.2.0", "18.3.1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_yarn_lock() {
+        let old = "\"@babel/code-frame@^7.0.0\", \"@babel/code-frame@^7.10.4\":\n  version \"7.10.4\"\n  resolved \"x\"\n\nleft-pad@^1.3.0:\n  version \"1.3.0\"\n";
+        let new = old.replace("7.10.4\"\n", "7.12.13\"\n");
+        assert_eq!(
+            Lockfile::Yarn.changes(old, &new),
+            vec![bumped("@babel/code-frame", "7.10.4", "7.12.13")]
+        );
+        // Berry (yarn 2+) syntax
+        let old = "\"react@npm:^18.0.0\":\n  version: 18.2.0\n";
+        let new = "\"react@npm:^18.0.0\":\n  version: 18.3.1\n";
+        assert_eq!(
+            Lockfile::Yarn.changes(old, new),
+            vec![bumped("react", "18.2.0", "18.3.1")]
+        );
+    }
+
+    #[test]
+    fn test_detect_and_display() {
+        assert_eq!(
+            Lockfile::detect(Path::new("web/package-lock.json")),
+            Some(Lockfile::Npm)
+        );
+        assert_eq!(
+            Lockfile::detect(Path::new("Cargo.lock")),
+            Some(Lockfile::Cargo)
+        );
+        assert_eq!(Lockfile::detect(Path::new("Gemfile.lock")), None);
+        assert_eq!(
+            bumped("serde", "1.0.227", "1.0.228").to_string(),
+            "serde bumped from 1.0.227 to 1.0.228"
+        );
+    }
+}
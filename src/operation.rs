@@ -0,0 +1,454 @@
+//! In-progress merges, reverts and cherry-picks.
+//!
+//! While git is in the middle of one of these, the index isn't an ordinary
+//! change set: git has already prepared a message (`.git/MERGE_MSG`) and the
+//! commit should follow its conventions (`Merge branch '...'`, `Revert "..."`
+//! with `This reverts commit <sha>.`, `(cherry picked from commit <sha>)`).
+//! The model only supplies the explanation, which is added to the body; the
+//! conventional parts come from git.
+
+use git2::{BranchType, Oid, Repository, RepositoryState};
+use std::fmt;
+use std::fs;
+
+use crate::templates::CommitTemplate;
+
+/// Which operation is in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    Merge,
+    Revert,
+    CherryPick,
+}
+
+/// An in-progress merge, revert or cherry-pick and the context git left for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub kind: OperationKind,
+    /// Heads being merged: branch names where known, otherwise short ids
+    pub merged: Vec<String>,
+    /// The commit being reverted or cherry-picked: full id and message
+    pub commit: Option<(String, String)>,
+    /// `MERGE_MSG` without its `#` comment lines
+    pub prepared_message: Option<String>,
+    /// Files git reported as conflicted (and that have since been resolved)
+    pub resolved_conflicts: Vec<String>,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            OperationKind::Merge => write!(f, "merge of {}", self.merged.join(", ")),
+            OperationKind::Revert => write!(f, "revert of {}", self.short_id()),
+            OperationKind::CherryPick => write!(f, "cherry-pick of {}", self.short_id()),
+        }
+    }
+}
+
+/// Detect an in-progress merge, revert or cherry-pick from the repository state.
+pub fn detect(repo: &Repository) -> Option<Operation> {
+    let kind = match repo.state() {
+        RepositoryState::Merge => OperationKind::Merge,
+        RepositoryState::Revert | RepositoryState::RevertSequence => OperationKind::Revert,
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+            OperationKind::CherryPick
+        }
+        _ => return None,
+    };
+
+    let raw_message = fs::read_to_string(repo.path().join("MERGE_MSG")).ok();
+    let prepared_message = raw_message
+        .as_deref()
+        .map(strip_comments)
+        .filter(|m| !m.is_empty());
+    let resolved_conflicts = raw_message
+        .as_deref()
+        .map(conflicted_files)
+        .unwrap_or_default();
+
+    let mut merged = Vec::new();
+    let mut commit = None;
+    match kind {
+        OperationKind::Merge => {
+            // `mergehead_foreach` needs `&mut Repository`; MERGE_HEAD is just
+            // one object id per line.
+            merged = fs::read_to_string(repo.path().join("MERGE_HEAD"))
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|line| Oid::from_str(line.trim()).ok())
+                .map(|oid| head_name(repo, oid))
+                .collect();
+        }
+        OperationKind::Revert | OperationKind::CherryPick => {
+            let head = if kind == OperationKind::Revert {
+                "REVERT_HEAD"
+            } else {
+                "CHERRY_PICK_HEAD"
+            };
+            commit = repo
+                .revparse_single(head)
+                .and_then(|obj| obj.peel_to_commit())
+                .ok()
+                .map(|c| {
+                    (
+                        c.id().to_string(),
+                        c.message().unwrap_or("").trim_end().to_string(),
+                    )
+                });
+        }
+    }
+
+    Some(Operation {
+        kind,
+        merged,
+        commit,
+        prepared_message,
+        resolved_conflicts,
+    })
+}
+
+/// A branch pointing at `oid` (local first), or its short id.
+fn head_name(repo: &Repository, oid: Oid) -> String {
+    for branch_type in [BranchType::Local, BranchType::Remote] {
+        let Ok(branches) = repo.branches(Some(branch_type)) else {
+            continue;
+        };
+        for (branch, _) in branches.flatten() {
+            if branch.get().target() == Some(oid) {
+                if let Ok(Some(name)) = branch.name() {
+                    return name.to_string();
+                }
+            }
+        }
+    }
+    oid.to_string()[..7].to_string()
+}
+
+/// Drop git's `#` comment lines (instructions, the `# Conflicts:` list).
+fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Paths listed under `Conflicts:` in `MERGE_MSG`, commented (`# Conflicts:`,
+/// current git) or not (older git).
+fn conflicted_files(message: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut in_conflicts = false;
+    for line in message.lines() {
+        let line = line.strip_prefix('#').unwrap_or(line);
+        if line.trim() == "Conflicts:" {
+            in_conflicts = true;
+        } else if in_conflicts {
+            match line.strip_prefix('\t') {
+                Some(path) if !path.trim().is_empty() => files.push(path.trim().to_string()),
+                _ if line.trim().is_empty() => {}
+                _ => in_conflicts = false,
+            }
+        }
+    }
+    files
+}
+
+impl Operation {
+    fn short_id(&self) -> &str {
+        self.commit
+            .as_ref()
+            .map(|(id, _)| &id[..id.len().min(7)])
+            .unwrap_or("unknown commit")
+    }
+
+    fn subject(&self) -> Option<&str> {
+        self.commit
+            .as_ref()
+            .and_then(|(_, message)| message.lines().next())
+    }
+
+    /// Appended to the system prompt so the model writes the explanation only.
+    pub fn instructions(&self) -> &'static str {
+        match self.kind {
+            OperationKind::Merge => {
+                "This commit concludes a merge. cmt writes git's \"Merge branch ...\" subject \
+                 line itself; your subject and details become the body, so summarize what the \
+                 merge brings in and how any conflicts were resolved."
+            }
+            OperationKind::Revert => {
+                "This commit reverts an earlier commit. cmt writes git's `Revert \"...\"` \
+                 subject and \"This reverts commit <sha>.\" line itself; your subject and details \
+                 become the body, so explain why the change is being reverted (use the hint if \
+                 one is given) and anything that differs from a plain revert."
+            }
+            OperationKind::CherryPick => {
+                "This commit cherry-picks an earlier commit. cmt keeps the original message \
+                 and adds git's \"(cherry picked from commit <sha>)\" line itself; your subject \
+                 and details are added to the body, so note why the change is picked onto this \
+                 branch and how it differs as applied here (resolved conflicts, adaptations)."
+            }
+        }
+    }
+
+    /// Context for the user prompt: what's being merged, reverted or picked.
+    pub fn context(&self) -> String {
+        let mut context = match self.kind {
+            OperationKind::Merge => format!(
+                "Merge in progress: merging {} into the current branch.\n",
+                self.merged.join(", ")
+            ),
+            OperationKind::Revert => format!("Revert in progress of commit {}.\n", self.short_id()),
+            OperationKind::CherryPick => {
+                format!("Cherry-pick in progress of commit {}.\n", self.short_id())
+            }
+        };
+        if let Some((_, message)) = &self.commit {
+            context.push_str(&format!("Original commit message:\n{}\n", message));
+        }
+        if !self.resolved_conflicts.is_empty() {
+            context.push_str(&format!(
+                "Files with resolved conflicts: {}\n",
+                self.resolved_conflicts.join(", ")
+            ));
+        }
+        context.push('\n');
+        context
+    }
+
+    /// The message git would write, with the model's explanation as the body.
+    pub fn format_message(&self, data: &CommitTemplate) -> String {
+        let mut explanation = capitalize(data.subject.trim());
+        if !explanation.is_empty() && !explanation.ends_with('.') {
+            explanation.push('.');
+        }
+        if let Some(details) = data.details.as_deref().filter(|d| !d.trim().is_empty()) {
+            explanation = format!("{}\n\n{}", explanation, details.trim());
+        }
+
+        match self.kind {
+            OperationKind::Merge => {
+                let subject = self
+                    .prepared_message
+                    .as_deref()
+                    .and_then(|m| m.lines().next())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| match self.merged.as_slice() {
+                        [one] => format!("Merge branch '{}'", one),
+                        many => format!("Merge branches {}", quote_list(many)),
+                    });
+                let mut message = format!("{}\n\n{}", subject, explanation);
+                // A plain paragraph: `git commit -F` keeps `#` lines as
+                // written, so git's commented list would land in the commit.
+                if !self.resolved_conflicts.is_empty() {
+                    message.push_str("\n\nConflicts:");
+                    for file in &self.resolved_conflicts {
+                        message.push_str(&format!("\n\t{}", file));
+                    }
+                }
+                message
+            }
+            OperationKind::Revert => {
+                let header = self.prepared_message.clone().unwrap_or_else(|| {
+                    format!(
+                        "Revert \"{}\"\n\nThis reverts commit {}.",
+                        self.subject().unwrap_or(""),
+                        self.commit
+                            .as_ref()
+                            .map(|(id, _)| id.as_str())
+                            .unwrap_or("")
+                    )
+                });
+                format!("{}\n\n{}", header, explanation)
+            }
+            OperationKind::CherryPick => {
+                let Some(original) = self
+                    .prepared_message
+                    .clone()
+                    .or_else(|| self.commit.as_ref().map(|(_, m)| m.clone()))
+                else {
+                    return explanation;
+                };
+                // The explanation goes after the original message and before
+                // the "(cherry picked from ...)" line, which stays last.
+                let (original, picked) = match original.rfind("(cherry picked from commit") {
+                    Some(at) => (
+                        original[..at].trim_end().to_string(),
+                        Some(original[at..].to_string()),
+                    ),
+                    None => (original, None),
+                };
+                let picked = picked.or_else(|| {
+                    self.commit
+                        .as_ref()
+                        .map(|(id, _)| format!("(cherry picked from commit {})", id))
+                });
+                let mut message = original;
+                if !explanation.is_empty() {
+                    message = format!("{}\n\n{}", message, explanation);
+                }
+                match picked {
+                    Some(picked) => format!("{}\n\n{}", message, picked),
+                    None => message,
+                }
+            }
+        }
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `'a', 'b' and 'c'`, as git writes multi-head merges.
+fn quote_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|i| format!("'{}'", i)).collect();
+    match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => quoted.join(""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn commit_file(repo: &Repository, name: &str, content: &str, message: &str) -> Oid {
+        fs::write(repo.workdir().unwrap().join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let parent = repo.head().and_then(|h| h.peel_to_commit()).ok();
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    fn explanation(subject: &str) -> CommitTemplate {
+        CommitTemplate {
+            subject: subject.to_string(),
+            ..CommitTemplate::default()
+        }
+    }
+
+    #[test]
+    fn test_no_operation_in_clean_repo() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a\n", "init");
+        assert!(detect(&repo).is_none());
+    }
+
+    #[test]
+    fn test_detect_revert_and_format() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a\n", "init");
+        let bad = commit_file(&repo, "a.txt", "b\n", "feat: switch to b");
+
+        let commit = repo.find_commit(bad).unwrap();
+        repo.revert(&commit, None).unwrap();
+
+        let op = detect(&repo).unwrap();
+        assert_eq!(op.kind, OperationKind::Revert);
+        assert_eq!(op.commit.as_ref().unwrap().0, bad.to_string());
+        assert!(op.context().contains("feat: switch to b"));
+
+        let message = op.format_message(&explanation("b broke the login flow"));
+        assert!(message.starts_with("Revert \"feat: switch to b\""));
+        assert!(message.contains(&format!("This reverts commit {}.", bad)));
+        assert!(message.ends_with("B broke the login flow."));
+    }
+
+    #[test]
+    fn test_detect_merge_with_conflicts() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let feature = commit_file(&repo, "a.txt", "base\n", "init");
+        repo.branch("feature", &repo.find_commit(feature).unwrap(), false)
+            .unwrap();
+        commit_file(&repo, "a.txt", "main\n", "main change");
+
+        // What `git merge feature` leaves behind after a conflict.
+        fs::write(repo.path().join("MERGE_HEAD"), format!("{}\n", feature)).unwrap();
+        fs::write(
+            repo.path().join("MERGE_MSG"),
+            "Merge branch 'feature'\n\n# Conflicts:\n#\ta.txt\n",
+        )
+        .unwrap();
+
+        let op = detect(&repo).unwrap();
+        assert_eq!(op.kind, OperationKind::Merge);
+        assert_eq!(op.merged, ["feature"]);
+        assert_eq!(op.resolved_conflicts, ["a.txt"]);
+        assert_eq!(
+            op.prepared_message.as_deref(),
+            Some("Merge branch 'feature'")
+        );
+
+        let message = op.format_message(&CommitTemplate {
+            subject: "bring in the feature flag".to_string(),
+            details: Some("- Keep main's default".to_string()),
+            ..CommitTemplate::default()
+        });
+        assert_eq!(
+            message,
+            "Merge branch 'feature'\n\nBring in the feature flag.\n\n- Keep main's default\n\nConflicts:\n\ta.txt"
+        );
+    }
+
+    #[test]
+    fn test_cherry_pick_keeps_original_message_and_adds_explanation() {
+        let op = Operation {
+            kind: OperationKind::CherryPick,
+            merged: Vec::new(),
+            commit: Some(("abc1234def".to_string(), "fix: handle nulls".to_string())),
+            prepared_message: Some("fix: handle nulls".to_string()),
+            resolved_conflicts: Vec::new(),
+        };
+        assert_eq!(
+            op.format_message(&explanation("backported for the 1.2 release")),
+            "fix: handle nulls\n\nBackported for the 1.2 release.\n\n(cherry picked from commit abc1234def)"
+        );
+        assert_eq!(op.to_string(), "cherry-pick of abc1234");
+
+        // With `-x`, git's line is already in the prepared message; it stays last.
+        let op = Operation {
+            prepared_message: Some(
+                "fix: handle nulls\n\n(cherry picked from commit abc1234def)".to_string(),
+            ),
+            ..op
+        };
+        assert_eq!(
+            op.format_message(&explanation("")),
+            "fix: handle nulls\n\n(cherry picked from commit abc1234def)"
+        );
+        assert_eq!(
+            op.format_message(&explanation("resolved the import conflict")),
+            "fix: handle nulls\n\nResolved the import conflict.\n\n(cherry picked from commit abc1234def)"
+        );
+    }
+
+    #[test]
+    fn test_merge_subject_without_merge_msg() {
+        let op = Operation {
+            kind: OperationKind::Merge,
+            merged: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            commit: None,
+            prepared_message: None,
+            resolved_conflicts: Vec::new(),
+        };
+        assert!(op
+            .format_message(&explanation("combine"))
+            .starts_with("Merge branches 'a', 'b' and 'c'\n\nCombine."));
+    }
+}
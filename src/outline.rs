@@ -0,0 +1,359 @@
+//! Enclosing definitions (functions, methods, classes, impls) for changed lines.
+//!
+//! With `--function-context`, each hunk is widened to the smallest definition
+//! around its changes instead of a fixed number of context lines, and the
+//! definitions' names are reported as the changed symbols. Files are parsed
+//! with tree-sitter grammars for Rust, JavaScript, TypeScript, Python, Go and
+//! Java; anything else keeps plain line context.
+
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Languages with a bundled grammar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Python,
+    Go,
+    Java,
+}
+
+impl Language {
+    /// The language of `path`, by extension.
+    pub fn detect(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "rs" => Some(Language::Rust),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "ts" | "mts" | "cts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            "py" | "pyi" => Some(Language::Python),
+            "go" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Language::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Language::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Language::Python => tree_sitter_python::LANGUAGE.into(),
+            Language::Go => tree_sitter_go::LANGUAGE.into(),
+            Language::Java => tree_sitter_java::LANGUAGE.into(),
+        }
+    }
+
+    /// Node kinds that count as a definition worth expanding to.
+    fn is_definition(self, kind: &str) -> bool {
+        match self {
+            Language::Rust => matches!(
+                kind,
+                "function_item"
+                    | "impl_item"
+                    | "trait_item"
+                    | "struct_item"
+                    | "enum_item"
+                    | "union_item"
+                    | "mod_item"
+                    | "macro_definition"
+            ),
+            Language::JavaScript | Language::TypeScript | Language::Tsx => matches!(
+                kind,
+                "function_declaration"
+                    | "generator_function_declaration"
+                    | "class_declaration"
+                    | "abstract_class_declaration"
+                    | "method_definition"
+                    | "interface_declaration"
+                    | "enum_declaration"
+                    | "type_alias_declaration"
+                    | "internal_module"
+                    | "variable_declarator"
+            ),
+            Language::Python => matches!(kind, "function_definition" | "class_definition"),
+            Language::Go => matches!(
+                kind,
+                "function_declaration" | "method_declaration" | "type_declaration"
+            ),
+            Language::Java => matches!(
+                kind,
+                "class_declaration"
+                    | "interface_declaration"
+                    | "enum_declaration"
+                    | "record_declaration"
+                    | "annotation_type_declaration"
+                    | "method_declaration"
+                    | "constructor_declaration"
+            ),
+        }
+    }
+
+    fn separator(self) -> &'static str {
+        match self {
+            Language::Rust => "::",
+            _ => ".",
+        }
+    }
+}
+
+/// A definition and the lines it spans (1-based, inclusive).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Qualified name, e.g. `DiffStats::print` or `Server.handle`
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Block {
+    pub fn line_count(&self) -> usize {
+        self.end + 1 - self.start
+    }
+}
+
+/// The definitions in one version of a file.
+#[derive(Debug, Default)]
+pub struct Outline {
+    blocks: Vec<Block>,
+}
+
+impl Outline {
+    /// Parse `source`. Syntax errors are tolerated; whatever definitions
+    /// tree-sitter recovers are kept.
+    pub fn parse(language: Language, source: &str) -> Self {
+        let mut parser = Parser::new();
+        if parser.set_language(&language.grammar()).is_err() {
+            return Self::default();
+        }
+        let Some(tree) = parser.parse(source, None) else {
+            return Self::default();
+        };
+
+        let mut blocks = Vec::new();
+        collect(
+            language,
+            source.as_bytes(),
+            tree.root_node(),
+            None,
+            &mut blocks,
+        );
+        Self { blocks }
+    }
+
+    /// The innermost definition containing `line` (1-based).
+    pub fn enclosing(&self, line: usize) -> Option<&Block> {
+        self.blocks
+            .iter()
+            .filter(|b| b.start <= line && line <= b.end)
+            .min_by_key(|b| b.line_count())
+    }
+}
+
+fn collect(
+    language: Language,
+    source: &[u8],
+    node: Node,
+    parent: Option<&str>,
+    blocks: &mut Vec<Block>,
+) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let name = if language.is_definition(child.kind()) {
+            definition_name(language, source, child)
+        } else {
+            None
+        };
+        match name {
+            Some((own, container)) => {
+                let qualified = match parent {
+                    Some(parent) => format!("{}{}{}", parent, language.separator(), own),
+                    None => own,
+                };
+                blocks.push(Block {
+                    name: qualified.clone(),
+                    start: child.start_position().row + 1,
+                    end: child.end_position().row + 1,
+                });
+                // Members of `impl Foo` are `Foo::bar`, not `impl Foo::bar`.
+                let prefix = match (container, parent) {
+                    (Some(container), Some(parent)) => {
+                        format!("{}{}{}", parent, language.separator(), container)
+                    }
+                    (Some(container), None) => container,
+                    (None, _) => qualified,
+                };
+                collect(language, source, child, Some(&prefix), blocks);
+            }
+            None => collect(language, source, child, parent, blocks),
+        }
+    }
+}
+
+/// The name a definition is shown as, plus the name its members are
+/// qualified with when that differs (`impl Display for Foo` → `Foo`).
+/// `None` for nodes that turn out not to be definitions (a plain variable).
+fn definition_name(
+    language: Language,
+    source: &[u8],
+    node: Node,
+) -> Option<(String, Option<String>)> {
+    let text = |n: Node| n.utf8_text(source).ok().map(str::to_string);
+    let field = |name: &str| node.child_by_field_name(name).and_then(text);
+
+    match node.kind() {
+        "impl_item" => {
+            let ty = field("type")?;
+            let name = match field("trait") {
+                Some(trait_name) => format!("impl {} for {}", trait_name, ty),
+                None => format!("impl {}", ty),
+            };
+            Some((name, Some(ty)))
+        }
+        // `const handler = () => {}` is a function; `const x = 1` is not.
+        "variable_declarator" => {
+            let value = node.child_by_field_name("value")?;
+            matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function" | "class"
+            )
+            .then(|| field("name"))
+            .flatten()
+            .map(|name| (name, None))
+        }
+        "type_declaration" => {
+            let mut cursor = node.walk();
+            let spec = node
+                .named_children(&mut cursor)
+                .find(|c| matches!(c.kind(), "type_spec" | "type_alias"))?;
+            let name = spec.child_by_field_name("name").and_then(text)?;
+            Some((name, None))
+        }
+        "method_declaration" if language == Language::Go => {
+            let name = field("name")?;
+            let receiver = node
+                .child_by_field_name("receiver")
+                .and_then(|r| find_kind(r, "type_identifier"))
+                .and_then(text);
+            Some((
+                match receiver {
+                    Some(receiver) => format!("{}.{}", receiver, name),
+                    None => name,
+                },
+                None,
+            ))
+        }
+        _ => field("name").map(|name| (name, None)),
+    }
+}
+
+fn find_kind<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node<'t>> = node.named_children(&mut cursor).collect();
+    children.into_iter().find_map(|c| find_kind(c, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_at(language: Language, source: &str, line: usize) -> Option<String> {
+        Outline::parse(language, source)
+            .enclosing(line)
+            .map(|b| b.name.clone())
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Language::detect(Path::new("src/git.rs")),
+            Some(Language::Rust)
+        );
+        assert_eq!(Language::detect(Path::new("App.tsx")), Some(Language::Tsx));
+        assert_eq!(Language::detect(Path::new("README.md")), None);
+        assert_eq!(Language::detect(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_rust_methods_are_qualified_by_impl_type() {
+        let source = "\
+use std::fmt;
+
+struct Stats {
+    files: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, \"{}\", self.files)
+    }
+}
+
+fn main() {}
+";
+        let outline = Outline::parse(Language::Rust, source);
+        let block = outline.enclosing(9).unwrap();
+        assert_eq!(block.name, "Stats::fmt");
+        assert_eq!((block.start, block.end), (8, 10));
+        assert_eq!(
+            name_at(Language::Rust, source, 7).as_deref(),
+            Some("impl fmt::Display for Stats")
+        );
+        assert_eq!(name_at(Language::Rust, source, 4).as_deref(), Some("Stats"));
+        assert_eq!(name_at(Language::Rust, source, 1), None);
+    }
+
+    #[test]
+    fn test_typescript_classes_and_arrow_functions() {
+        let source = "\
+export class Server {
+  handle(req: Request): Response {
+    return ok(req);
+  }
+}
+
+const retry = async (n: number) => {
+  return n + 1;
+};
+
+const limit = 3;
+";
+        assert_eq!(
+            name_at(Language::TypeScript, source, 3).as_deref(),
+            Some("Server.handle")
+        );
+        assert_eq!(
+            name_at(Language::TypeScript, source, 8).as_deref(),
+            Some("retry")
+        );
+        assert_eq!(name_at(Language::TypeScript, source, 11), None);
+    }
+
+    #[test]
+    fn test_python_go_and_java() {
+        let python = "class Cache:\n    def get(self, key):\n        return self.d[key]\n";
+        assert_eq!(
+            name_at(Language::Python, python, 3).as_deref(),
+            Some("Cache.get")
+        );
+
+        let go = "package main\n\nfunc (s *Server) Start() error {\n\treturn nil\n}\n";
+        assert_eq!(
+            name_at(Language::Go, go, 4).as_deref(),
+            Some("Server.Start")
+        );
+
+        let java = "class Greeter {\n  String greet(String name) {\n    return name;\n  }\n}\n";
+        assert_eq!(
+            name_at(Language::Java, java, 3).as_deref(),
+            Some("Greeter.greet")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Machine-readable (`--output json`) reports for editor and CI integrations.
+//!
+//! A run emits exactly one JSON document on stdout: either a [`JsonReport`]
+//! describing the generated message, or an [`ErrorReport`] whose `kind` is
+//! derived from the typed [`AiError`] / [`CommitError`] so callers can branch
+//! on it without parsing prose.
+
+use serde::Serialize;
+use std::error::Error;
+
+use crate::ai::AiError;
+use crate::commit::CommitError;
+use crate::engine::{secrets_summary, CmtError};
+use crate::git::DiffStats;
+use crate::pricing::{self, PricingCache};
+use crate::redact::Finding;
+use crate::templates::CommitTemplate;
+
+/// Output format selected with `--output`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable terminal output
+    #[default]
+    Text,
+    /// A single JSON document on stdout
+    Json,
+}
+
+/// Token usage for the generation, flagged when estimated (~4 chars/token)
+/// because the provider didn't report it.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated: bool,
+}
+
+impl UsageReport {
+    /// Token counts reported by the provider, or an estimate (~4 chars/token)
+    /// from the prompt and message lengths when it didn't report them.
+    pub fn new(
+        input_tokens: Option<u64>,
+        output_tokens: Option<u64>,
+        prompt_len: usize,
+        message: &str,
+    ) -> Self {
+        match (input_tokens, output_tokens) {
+            (Some(input_tokens), Some(output_tokens)) => Self {
+                input_tokens,
+                output_tokens,
+                estimated: false,
+            },
+            _ => Self {
+                input_tokens: (prompt_len as u64) / 4,
+                output_tokens: (message.len() as u64) / 4,
+                estimated: true,
+            },
+        }
+    }
+
+    /// Cost in USD from the LiteLLM pricing data, if the model is listed.
+    pub fn cost_usd(&self, pricing: &mut PricingCache, provider: &str, model: &str) -> Option<f64> {
+        pricing
+            .get_model_pricing(provider, model)
+            .and_then(|p| pricing::calculate_cost(&p, self.input_tokens, self.output_tokens))
+    }
+}
+
+/// The commit created with `-y`, if any.
+#[derive(Debug, Serialize)]
+pub struct CommitReport {
+    pub oid: String,
+}
+
+/// Successful run: structured template, rendered message, and run metadata.
+#[derive(Debug, Serialize)]
+pub struct JsonReport<'a> {
+    pub template: &'a CommitTemplate,
+    pub message: &'a str,
+    pub stats: &'a DiffStats,
+    pub redactions: usize,
+    /// Where each redacted secret was and which rule found it
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub secrets: &'a [Finding],
+    /// Distinct personal data values replaced with placeholders
+    pub pii: usize,
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub usage: UsageReport,
+    pub cost_usd: Option<f64>,
+    pub elapsed_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitReport>,
+}
+
+/// `--fixup` run: the target commit and the `fixup!` message.
+#[derive(Debug, Serialize)]
+pub struct FixupReport<'a> {
+    pub target: &'a str,
+    pub subject: &'a str,
+    pub message: &'a str,
+    /// Staged pre-image lines last touched by the target (0 when given explicitly)
+    pub matched_lines: usize,
+    pub total_lines: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitReport>,
+}
+
+/// Failed run: a stable `kind` plus the human-readable message.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub kind: String,
+    pub message: String,
+    /// Extra detail when available (hook output, signing hint, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    /// The secrets that stopped a `--fail-on-secrets` run, or that a
+    /// `review_redactions` server request must confirm
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<Finding>,
+}
+
+impl ErrorReport {
+    pub fn new(kind: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            message: message.into(),
+            details: None,
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Classify an error, using the typed cmt errors where possible.
+    pub fn from_error(err: &(dyn Error + 'static)) -> Self {
+        if let Some(e) = err.downcast_ref::<CmtError>() {
+            return match e {
+                CmtError::Ai(inner) => Self::from_error(inner),
+                CmtError::Commit(inner) => Self::from_error(inner),
+                CmtError::SecretsFound(findings) => Self {
+                    secrets: findings.clone(),
+                    ..Self::new(e.kind(), secrets_summary(findings))
+                },
+                _ => Self::new(e.kind(), e.to_string()),
+            };
+        }
+        if let Some(e) = err.downcast_ref::<AiError>() {
+            return Self::new(e.kind(), e.to_string());
+        }
+        if let Some(e) = err.downcast_ref::<CommitError>() {
+            let details = match e {
+                CommitError::PreCommitFailed { .. } | CommitError::CommitMsgFailed { .. } => {
+                    e.hook_output().map(str::to_string)
+                }
+                CommitError::SigningFailed { output } => Some(output.trim().to_string()),
+                _ => None,
+            };
+            return Self {
+                details,
+                ..Self::new(e.kind(), e.to_string())
+            };
+        }
+        if err.downcast_ref::<git2::Error>().is_some() {
+            return Self::new("git_error", err.to_string());
+        }
+        Self::new("error", err.to_string())
+    }
+
+    /// Wrap the report as `{"error": {...}}`.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({ "error": self }).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::FileStat;
+
+    #[test]
+    fn test_error_report_kinds() {
+        let err: Box<dyn Error> = Box::new(AiError::RateLimited {
+            provider: "openai".to_string(),
+        });
+        assert_eq!(ErrorReport::from_error(err.as_ref()).kind, "rate_limited");
+
+        let err: Box<dyn Error> = Box::new(CommitError::PreCommitFailed {
+            output: "lint failed\n".to_string(),
+        });
+        let report = ErrorReport::from_error(err.as_ref());
+        assert_eq!(report.kind, "pre_commit_failed");
+        assert_eq!(report.details.as_deref(), Some("lint failed"));
+
+        let err: Box<dyn Error> = Box::new(CmtError::Commit(CommitError::ParseError));
+        assert_eq!(ErrorReport::from_error(err.as_ref()).kind, "parse_error");
+
+        let err: Box<dyn Error> = "something else".into();
+        assert_eq!(ErrorReport::from_error(err.as_ref()).kind, "error");
+    }
+
+    #[test]
+    fn test_error_report_json_shape() {
+        let json: serde_json::Value =
+            serde_json::from_str(&ErrorReport::new("nothing_staged", "No changes").to_json())
+                .unwrap();
+        assert_eq!(json["error"]["kind"], "nothing_staged");
+        assert_eq!(json["error"]["message"], "No changes");
+        assert!(json["error"].get("details").is_none());
+    }
+
+    #[test]
+    fn test_usage_estimates_when_provider_is_silent() {
+        let reported = UsageReport::new(Some(100), Some(20), 4000, "msg");
+        assert_eq!(reported.input_tokens, 100);
+        assert!(!reported.estimated);
+
+        let estimated = UsageReport::new(None, None, 4000, "feat: add login");
+        assert_eq!(estimated.input_tokens, 1000);
+        assert_eq!(estimated.output_tokens, 3);
+        assert!(estimated.estimated);
+    }
+
+    #[test]
+    fn test_stats_serialize_as_objects() {
+        let stats = DiffStats {
+            files_changed: 2,
+            insertions: 3,
+            deletions: 1,
+            file_changes: vec![FileStat::new("src/lib.rs", 3, 1)],
+            skipped_files: Vec::new(),
+            ignored_files: vec![FileStat {
+                reason: Some("lockfile".to_string()),
+                ..FileStat::new("Cargo.lock", 0, 0)
+            }],
+            dependency_changes: Vec::new(),
+            submodule_updates: Vec::new(),
+            budget: None,
+            has_unstaged: false,
+        };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["file_changes"][0]["path"], "src/lib.rs");
+        assert_eq!(json["file_changes"][0]["insertions"], 3);
+        assert!(json["file_changes"][0].get("reason").is_none());
+        assert_eq!(json["ignored_files"][0]["path"], "Cargo.lock");
+        assert_eq!(json["ignored_files"][0]["reason"], "lockfile");
+        assert_eq!(json["ignored_files"][0]["kind"], "modified");
+        assert!(json.get("dependency_changes").is_none());
+    }
+}
@@ -23,6 +23,9 @@ pub struct ModelPricing {
     pub input_cost_per_token: Option<f64>,
     #[serde(default)]
     pub output_cost_per_token: Option<f64>,
+    /// Size of the model's input window
+    #[serde(default)]
+    pub max_input_tokens: Option<u64>,
 }
 
 /// Raw JSON value for flexible parsing
@@ -39,6 +42,7 @@ fn parse_pricing_data(raw: RawPricingData) -> HashMap<String, ModelPricing> {
             // Try to extract pricing fields
             let input_cost = value.get("input_cost_per_token").and_then(|v| v.as_f64());
             let output_cost = value.get("output_cost_per_token").and_then(|v| v.as_f64());
+            let max_input_tokens = value.get("max_input_tokens").and_then(|v| v.as_u64());
 
             // Only include if we have pricing data
             if input_cost.is_some() || output_cost.is_some() {
@@ -47,6 +51,7 @@ fn parse_pricing_data(raw: RawPricingData) -> HashMap<String, ModelPricing> {
                     ModelPricing {
                         input_cost_per_token: input_cost,
                         output_cost_per_token: output_cost,
+                        max_input_tokens,
                     },
                 ))
             } else {
@@ -178,6 +183,26 @@ fn generate_model_keys(provider: &str, model: &str) -> Vec<String> {
     keys
 }
 
+/// The input window of `model` in already-loaded pricing data.
+fn find_max_input_tokens(
+    data: &HashMap<String, ModelPricing>,
+    provider: &str,
+    model: &str,
+) -> Option<u64> {
+    generate_model_keys(provider, model)
+        .iter()
+        .find_map(|key| data.get(key)?.max_input_tokens)
+}
+
+/// The input window of `model` from the on-disk pricing cache, however old.
+/// Reads synchronously and never fetches, so it is `None` until a previous
+/// run (or the background fetch) has cached the data.
+pub fn cached_max_input_tokens(provider: &str, model: &str) -> Option<u64> {
+    let contents = fs::read_to_string(cache_file()?).ok()?;
+    let raw: RawPricingData = serde_json::from_str(&contents).ok()?;
+    find_max_input_tokens(&parse_pricing_data(raw), provider, model)
+}
+
 /// Get the cache directory path (~/.cache/cmt on all platforms)
 fn cache_dir() -> Option<PathBuf> {
     dirs::home_dir().map(|p| p.join(".cache").join("cmt"))
@@ -326,12 +351,36 @@ mod tests {
         let pricing = ModelPricing {
             input_cost_per_token: Some(0.000001),
             output_cost_per_token: Some(0.000002),
+            max_input_tokens: None,
         };
 
         let cost = calculate_cost(&pricing, 1000, 500);
         assert_eq!(cost, Some(0.002)); // 1000 * 0.000001 + 500 * 0.000002
     }
 
+    #[test]
+    fn test_max_input_tokens_lookup() {
+        let raw: RawPricingData = serde_json::from_str(
+            r#"{
+                "sample_spec": {"max_input_tokens": 1},
+                "gemini/gemini-3-flash": {
+                    "input_cost_per_token": 1e-7,
+                    "output_cost_per_token": 4e-7,
+                    "max_input_tokens": 1048576
+                },
+                "gpt-5.2": {"input_cost_per_token": 1e-6, "max_input_tokens": "n/a"}
+            }"#,
+        )
+        .unwrap();
+        let data = parse_pricing_data(raw);
+        assert_eq!(
+            find_max_input_tokens(&data, "gemini", "gemini-3-flash-preview"),
+            Some(1048576)
+        );
+        assert_eq!(find_max_input_tokens(&data, "openai", "gpt-5.2"), None);
+        assert_eq!(find_max_input_tokens(&data, "claude", "unknown"), None);
+    }
+
     #[test]
     fn test_cache_dir() {
         let dir = super::cache_dir();
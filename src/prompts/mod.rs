@@ -1,26 +1,492 @@
+use std::fs;
+use std::path::Path;
+
+use handlebars::Handlebars;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::git::{BranchCommit, ChangeKind, DiffStats, FileStat};
+use crate::templates::TemplateError;
+
 pub static SYSTEM_PROMPT: &str = include_str!("system_prompt.txt");
 pub static USER_PROMPT_TEMPLATE: &str = include_str!("user_prompt.txt");
+/// Appended to the system prompt for `cmt squash`.
+pub static SQUASH_PROMPT: &str = include_str!("squash_prompt.txt");
+/// Appended to the system prompt when `[placeholders]` tokens were sent.
+pub static PLACEHOLDERS_PROMPT: &str = include_str!("placeholders_prompt.txt");
+
+/// Directory (relative to the repository root) holding per-project prompt files.
+pub const PROMPTS_DIRNAME: &str = ".cmt/prompts";
+
+/// Variables available to prompt templates (built-in or overridden).
+///
+/// Missing values render as empty strings, so `{{#if branch}}` guards work.
+#[derive(Debug, Default, Serialize)]
+pub struct PromptVars<'a> {
+    pub changes: &'a str,
+    pub branch: Option<&'a str>,
+    pub recent_commits: Option<&'a str>,
+    pub readme: Option<&'a str>,
+    pub hint: Option<&'a str>,
+}
+
+/// The "commits being squashed" section of the user prompt, oldest first.
+pub fn squash_section(commits: &[BranchCommit]) -> String {
+    let mut section = String::from("Commits being squashed (oldest first):\n");
+    for commit in commits {
+        section.push_str(&format!("[{}] {}\n", commit.id, commit.message));
+    }
+    section.push('\n');
+    section
+}
+
+/// Entries listed per part of [`excluded_section`] before the rest are elided.
+const MAX_EXCLUDED_ENTRIES: usize = 100;
+
+/// The "changed but not shown" section of the user prompt: ignored and skipped
+/// files with their line counts, change kind and reason, files cut to fit the
+/// token budget, plus dependency changes read from lockfiles. Empty when the
+/// diff shows every file in full.
+pub fn excluded_section(stats: &DiffStats) -> String {
+    let excluded: Vec<_> = stats
+        .ignored_files
+        .iter()
+        .chain(&stats.skipped_files)
+        .collect();
+    let cut = stats
+        .budget
+        .as_ref()
+        .map_or(&[][..], |budget| budget.cut_files.as_slice());
+    if excluded.is_empty() && cut.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::new();
+    if !excluded.is_empty() {
+        section.push_str("Changed files not shown in the diff:\n");
+        for file in excluded.iter().take(MAX_EXCLUDED_ENTRIES) {
+            section.push_str(&format!("- {}", file_entry(file)));
+            if let Some(reason) = &file.reason {
+                section.push_str(&format!(": {}", reason));
+            }
+            section.push('\n');
+        }
+        push_elided(&mut section, excluded.len());
+    }
+
+    if !cut.is_empty() {
+        if !section.is_empty() {
+            section.push('\n');
+        }
+        section.push_str("Cut from the diff to fit the token budget:\n");
+        for file in cut.iter().take(MAX_EXCLUDED_ENTRIES) {
+            section.push_str(&format!("- {}: {}\n", file.path, file.describe()));
+        }
+        push_elided(&mut section, cut.len());
+    }
+
+    if !stats.dependency_changes.is_empty() {
+        section.push_str("\nDependency changes (from lockfiles):\n");
+        for change in stats.dependency_changes.iter().take(MAX_EXCLUDED_ENTRIES) {
+            section.push_str(&format!("- {}\n", change));
+        }
+        push_elided(&mut section, stats.dependency_changes.len());
+    }
+    section.push('\n');
+    section
+}
+
+/// The file header section of the user prompt: shown files whose change the
+/// diff body alone doesn't make obvious (renames, copies, mode changes,
+/// binaries, submodules). Empty when there are none.
+pub fn file_kinds_section(stats: &DiffStats) -> String {
+    let notable: Vec<&FileStat> = stats
+        .file_changes
+        .iter()
+        .filter(|f| {
+            !matches!(
+                f.kind,
+                ChangeKind::Modified | ChangeKind::Added | ChangeKind::Deleted
+            )
+        })
+        .collect();
+    if notable.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from("File changes:\n");
+    for file in notable.iter().take(MAX_EXCLUDED_ENTRIES) {
+        section.push_str(&format!("- {}\n", file_entry(file)));
+    }
+    push_elided(&mut section, notable.len());
+    section.push('\n');
+    section
+}
+
+/// The submodule section of the user prompt: each pointer bump with the
+/// subjects of the upstream commits it brings in. Empty without submodule
+/// changes.
+pub fn submodules_section(stats: &DiffStats) -> String {
+    if stats.submodule_updates.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from("Submodule updates:\n");
+    for update in &stats.submodule_updates {
+        section.push_str(&format!("- {}\n", update));
+        for subject in &update.commits {
+            section.push_str(&format!("  - {}\n", subject));
+        }
+        let more = update.commit_count.saturating_sub(update.commits.len());
+        if more > 0 {
+            section.push_str(&format!("  - ... and {} more\n", more));
+        }
+    }
+    section.push('\n');
+    section
+}
+
+/// The "changed symbols" section of the user prompt: the functions, methods
+/// and types each file's changes fall in (`--function-context` only). Empty
+/// when none were found.
+pub fn symbols_section(stats: &DiffStats) -> String {
+    let files: Vec<&FileStat> = stats
+        .file_changes
+        .iter()
+        .filter(|f| !f.symbols.is_empty())
+        .collect();
+    if files.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from("Changed symbols:\n");
+    for file in files.iter().take(MAX_EXCLUDED_ENTRIES) {
+        section.push_str(&format!("- {}: {}\n", file.path, file.symbols.join(", ")));
+    }
+    push_elided(&mut section, files.len());
+    section.push('\n');
+    section
+}
+
+/// `path (kind, +a -d)`
+fn file_entry(file: &FileStat) -> String {
+    format!(
+        "{} ({}, +{} -{})",
+        file.path,
+        file.describe_kind(),
+        file.insertions,
+        file.deletions
+    )
+}
+
+fn push_elided(section: &mut String, total: usize) {
+    if total > MAX_EXCLUDED_ENTRIES {
+        section.push_str(&format!(
+            "- ... and {} more\n",
+            total - MAX_EXCLUDED_ENTRIES
+        ));
+    }
+}
+
+/// Render a prompt template with Handlebars.
+///
+/// The model reads the full diff directly via `{{changes}}`; cmt no longer
+/// pre-digests it with a hand-rolled analysis layer (see the dropped
+/// `analysis` module).
+///
+/// Escaping is disabled: prompts are plain text, and HTML-escaping would mangle
+/// the diff (`<`, `>`, `&` are everywhere in code). Values are never re-parsed
+/// as templates, so `{{` inside the diff itself is harmless.
+pub fn render(template: &str, vars: &PromptVars) -> Result<String, TemplateError> {
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(handlebars::no_escape);
+    hb.render_template(template, vars)
+        .map_err(|e| TemplateError::RenderError(e.to_string()))
+}
+
+/// Whether `template` uses the variable `name`, as `{{name}}`, `{{{name}}}`
+/// or in a block helper such as `{{#if name}}`.
+pub fn uses_var(template: &str, name: &str) -> bool {
+    let pattern = format!(
+        r"\{{\{{\{{?~?\s*(?:#(?:if|unless|with)\s+)?{}\b",
+        regex::escape(name)
+    );
+    Regex::new(&pattern).is_ok_and(|re| re.is_match(template))
+}
+
+/// Resolve the system prompt template: an override replaces the built-in text,
+/// and an append is added after it as house rules.
+pub fn system_template(config: &Config) -> String {
+    with_append(
+        config.system_prompt.as_deref().unwrap_or(SYSTEM_PROMPT),
+        config.system_prompt_append.as_deref(),
+    )
+}
+
+/// Resolve the user prompt template, mirroring [`system_template`].
+pub fn user_template(config: &Config) -> String {
+    with_append(
+        config
+            .user_prompt
+            .as_deref()
+            .unwrap_or(USER_PROMPT_TEMPLATE),
+        config.user_prompt_append.as_deref(),
+    )
+}
 
-pub fn system_prompt() -> String {
-    SYSTEM_PROMPT.to_string()
+fn with_append(base: &str, append: Option<&str>) -> String {
+    match append.map(str::trim).filter(|a| !a.is_empty()) {
+        Some(extra) => format!("{}\n\n{}", base.trim_end(), extra),
+        None => base.to_string(),
+    }
 }
 
-/// Generate the user prompt for a diff.
+/// Fill prompt overrides from `<repo_root>/.cmt/prompts/*.txt`.
 ///
-/// The model reads the full diff directly; cmt no longer pre-digests it with a
-/// hand-rolled analysis layer (see the dropped `analysis` module).
-pub fn user_prompt(changes: &str) -> String {
-    USER_PROMPT_TEMPLATE.replace("{{changes}}", changes)
+/// Recognized files are `system.txt` / `user.txt` (replace the built-in prompt)
+/// and `system.append.txt` / `user.append.txt` (appended to it). Keys already
+/// set in `.cmt.toml` take precedence over the files.
+pub fn load_project_prompts(config: &mut Config, repo_root: &Path) {
+    let dir = repo_root.join(PROMPTS_DIRNAME);
+    if !dir.is_dir() {
+        return;
+    }
+
+    let read = |name: &str| {
+        fs::read_to_string(dir.join(name))
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+    };
+
+    if config.system_prompt.is_none() {
+        config.system_prompt = read("system.txt");
+    }
+    if config.system_prompt_append.is_none() {
+        config.system_prompt_append = read("system.append.txt");
+    }
+    if config.user_prompt.is_none() {
+        config.user_prompt = read("user.txt");
+    }
+    if config.user_prompt_append.is_none() {
+        config.user_prompt_append = read("user.append.txt");
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_user_prompt_embeds_diff() {
-        let prompt = user_prompt("test diff");
+        let vars = PromptVars {
+            changes: "test diff",
+            ..Default::default()
+        };
+        let prompt = render(USER_PROMPT_TEMPLATE, &vars).unwrap();
         assert!(prompt.contains("test diff"));
         assert!(!prompt.contains("Pre-Analysis"));
     }
+
+    #[test]
+    fn test_render_builtin_user_template_does_not_escape() {
+        let vars = PromptVars {
+            changes: "+fn a() -> Vec<&str> {}",
+            ..Default::default()
+        };
+        let rendered = render(USER_PROMPT_TEMPLATE, &vars).unwrap();
+        // No HTML escaping, and identical to a plain substitution.
+        assert_eq!(
+            rendered,
+            USER_PROMPT_TEMPLATE.replace("{{changes}}", vars.changes)
+        );
+    }
+
+    #[test]
+    fn test_render_does_not_reparse_diff_braces() {
+        let vars = PromptVars {
+            changes: "+let t = \"{{subject}}\";",
+            ..Default::default()
+        };
+        let rendered = render("diff: {{changes}}", &vars).unwrap();
+        assert_eq!(rendered, "diff: +let t = \"{{subject}}\";");
+    }
+
+    #[test]
+    fn test_override_and_append() {
+        let config = Config {
+            user_prompt: Some("Describe {{changes}}{{#if branch}} on {{branch}}{{/if}}".into()),
+            system_prompt_append: Some("Always mention the Jira ticket.\n".into()),
+            ..Config::default()
+        };
+
+        let system = system_template(&config);
+        assert!(system.starts_with(SYSTEM_PROMPT.trim_end()));
+        assert!(system.ends_with("Always mention the Jira ticket."));
+
+        let vars = PromptVars {
+            changes: "+x",
+            branch: Some("feat/PROJ-9"),
+            ..Default::default()
+        };
+        let user = render(&user_template(&config), &vars).unwrap();
+        assert_eq!(user, "Describe +x on feat/PROJ-9");
+    }
+
+    #[test]
+    fn test_excluded_section_lists_files_and_dependencies() {
+        use crate::git::FileStat;
+        use crate::lockfile::DependencyChange;
+
+        assert_eq!(excluded_section(&DiffStats::default()), "");
+
+        let stats = DiffStats {
+            ignored_files: vec![FileStat {
+                reason: Some("lockfile".to_string()),
+                ..FileStat::new("Cargo.lock", 2, 2)
+            }],
+            skipped_files: vec![FileStat {
+                reason: Some("over 5000 lines".to_string()),
+                ..FileStat::new("migrations/0042.sql", 10234, 0)
+            }],
+            dependency_changes: vec![DependencyChange::Bumped {
+                name: "serde".to_string(),
+                from: "1.0.227".to_string(),
+                to: "1.0.228".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            excluded_section(&stats),
+            "Changed files not shown in the diff:\n\
+             - Cargo.lock (modified, +2 -2): lockfile\n\
+             - migrations/0042.sql (modified, +10234 -0): over 5000 lines\n\
+             \n\
+             Dependency changes (from lockfiles):\n\
+             - serde bumped from 1.0.227 to 1.0.228\n\n"
+        );
+    }
+
+    #[test]
+    fn test_submodules_section_lists_upstream_commits() {
+        use crate::submodule::SubmoduleUpdate;
+
+        assert_eq!(submodules_section(&DiffStats::default()), "");
+
+        let stats = DiffStats {
+            submodule_updates: vec![SubmoduleUpdate {
+                path: "vendor/foo".to_string(),
+                old_commit: Some("1a2b3c4".to_string()),
+                new_commit: Some("5d6e7f8".to_string()),
+                new_tag: Some("v2.3".to_string()),
+                commits: vec!["Add retry with backoff".to_string()],
+                commit_count: 5,
+                history_available: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            submodules_section(&stats),
+            "Submodule updates:\n\
+             - vendor/foo: 1a2b3c4 -> 5d6e7f8 (v2.3), 5 commits\n  \
+             - Add retry with backoff\n  \
+             - ... and 4 more\n\n"
+        );
+    }
+
+    #[test]
+    fn test_excluded_section_lists_budget_cuts() {
+        use crate::budget::{BudgetReport, CutFile};
+
+        let stats = DiffStats {
+            budget: Some(BudgetReport {
+                max_tokens: 1000,
+                used_tokens: 990,
+                cut_files: vec![
+                    CutFile {
+                        path: "src/engine.rs".to_string(),
+                        hunks_omitted: 2,
+                        hunks_total: 5,
+                    },
+                    CutFile {
+                        path: "tests/fixtures/big.json".to_string(),
+                        hunks_omitted: 1,
+                        hunks_total: 1,
+                    },
+                ],
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            excluded_section(&stats),
+            "Cut from the diff to fit the token budget:\n\
+             - src/engine.rs: 2 of 5 hunks left out\n\
+             - tests/fixtures/big.json: left out\n\n"
+        );
+    }
+
+    #[test]
+    fn test_file_kinds_section_lists_renames_and_modes() {
+        let stats = DiffStats {
+            file_changes: vec![
+                FileStat::new("src/lib.rs", 4, 1),
+                FileStat {
+                    kind: ChangeKind::Renamed,
+                    old_path: Some("src/old.rs".to_string()),
+                    ..FileStat::new("src/new.rs", 1, 1)
+                },
+                FileStat {
+                    kind: ChangeKind::ModeChange,
+                    mode: Some("100644 -> 100755".to_string()),
+                    ..FileStat::new("run.sh", 0, 0)
+                },
+                FileStat {
+                    kind: ChangeKind::Binary,
+                    ..FileStat::new("logo.ico", 0, 0)
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            file_kinds_section(&stats),
+            "File changes:\n\
+             - src/new.rs (renamed from src/old.rs, +1 -1)\n\
+             - run.sh (mode change 100644 -> 100755, +0 -0)\n\
+             - logo.ico (binary, +0 -0)\n\n"
+        );
+        assert_eq!(file_kinds_section(&DiffStats::default()), "");
+    }
+
+    #[test]
+    fn test_uses_var() {
+        assert!(uses_var("Context: {{readme}}", "readme"));
+        assert!(uses_var("{{#if branch}}On {{ branch }}{{/if}}", "branch"));
+        assert!(uses_var("{{{ recent_commits }}}", "recent_commits"));
+        assert!(!uses_var("{{readme_title}} and readme", "readme"));
+        assert!(!uses_var(USER_PROMPT_TEMPLATE, "hint"));
+    }
+
+    #[test]
+    fn test_load_project_prompts_respects_config_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join(PROMPTS_DIRNAME);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("system.append.txt"), "Never mention tests.").unwrap();
+        fs::write(dir.join("user.txt"), "from file").unwrap();
+
+        let mut config = Config {
+            user_prompt: Some("from config".into()),
+            ..Config::default()
+        };
+        load_project_prompts(&mut config, temp_dir.path());
+
+        assert_eq!(
+            config.system_prompt_append.as_deref(),
+            Some("Never mention tests.")
+        );
+        assert_eq!(config.user_prompt.as_deref(), Some("from config"));
+        assert!(config.system_prompt.is_none());
+    }
 }
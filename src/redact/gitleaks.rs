@@ -0,0 +1,210 @@
+//! Extra secret rules and allowlists from a gitleaks-compatible TOML file.
+//!
+//! The file uses the gitleaks config format: `[[rules]]` with `id`, `regex`,
+//! `secretGroup`, `entropy`, `keywords`, `path` and per-rule allowlists, a
+//! global `[allowlist]` (or `[[allowlists]]`), and `[extend] disabledRules` to
+//! turn off built-in rules by id. Rules are added to the built-in catalog
+//! rather than replacing it. Path-only rules (no `regex`) flag files rather
+//! than values and are skipped, as are fields that only matter for scanning
+//! history (`commits`, `tags`).
+
+use regex::Regex;
+use serde::Deserialize;
+
+use super::rules::{Allowlist, SecretRule, Target};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GitleaksFile {
+    extend: Extend,
+    rules: Vec<RuleSpec>,
+    allowlist: Option<AllowlistSpec>,
+    allowlists: Vec<AllowlistSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Extend {
+    disabled_rules: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RuleSpec {
+    id: String,
+    description: String,
+    regex: Option<String>,
+    secret_group: usize,
+    entropy: Option<f64>,
+    keywords: Vec<String>,
+    path: Option<String>,
+    allowlist: Option<AllowlistSpec>,
+    allowlists: Vec<AllowlistSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct AllowlistSpec {
+    condition: Option<String>,
+    regexes: Vec<String>,
+    regex_target: Option<String>,
+    stopwords: Vec<String>,
+    paths: Vec<String>,
+}
+
+/// What a rules file contributes.
+#[derive(Debug, Default)]
+pub(crate) struct RuleFile {
+    pub rules: Vec<SecretRule>,
+    pub allowlists: Vec<Allowlist>,
+    pub disabled_rules: Vec<String>,
+}
+
+/// Parse a gitleaks config. Errors name the rule or allowlist at fault.
+pub(crate) fn parse(content: &str) -> Result<RuleFile, String> {
+    let file: GitleaksFile = toml::from_str(content).map_err(|e| e.to_string())?;
+
+    let mut rules = Vec::new();
+    for spec in file.rules {
+        let Some(regex) = &spec.regex else {
+            continue;
+        };
+        let context = |e: String| format!("rule {}: {}", spec.id, e);
+        let mut allowlists = Vec::new();
+        for allowlist in spec.allowlist.iter().chain(&spec.allowlists) {
+            allowlists.push(allowlist.build().map_err(context)?);
+        }
+        rules.push(SecretRule {
+            id: spec.id.clone(),
+            description: spec.description.clone(),
+            regex: compile(regex).map_err(context)?,
+            secret_group: spec.secret_group,
+            keywords: spec.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            entropy: spec.entropy.filter(|&e| e > 0.0),
+            path: spec
+                .path
+                .as_deref()
+                .map(compile)
+                .transpose()
+                .map_err(context)?,
+            allowlists,
+        });
+    }
+
+    let allowlists = file
+        .allowlist
+        .iter()
+        .chain(&file.allowlists)
+        .map(|allowlist| allowlist.build().map_err(|e| format!("allowlist: {}", e)))
+        .collect::<Result<_, _>>()?;
+
+    Ok(RuleFile {
+        rules,
+        allowlists,
+        disabled_rules: file.extend.disabled_rules,
+    })
+}
+
+impl AllowlistSpec {
+    fn build(&self) -> Result<Allowlist, String> {
+        let all = match self.condition.as_deref().map(str::to_uppercase).as_deref() {
+            None | Some("OR") => false,
+            Some("AND") => true,
+            Some(other) => return Err(format!("unknown condition {:?}", other)),
+        };
+        let target = match self.regex_target.as_deref() {
+            None | Some("secret") => Target::Secret,
+            Some("match") => Target::Match,
+            Some("line") => Target::Line,
+            Some(other) => return Err(format!("unknown regexTarget {:?}", other)),
+        };
+        Ok(Allowlist {
+            all,
+            regexes: self
+                .regexes
+                .iter()
+                .map(|r| compile(r))
+                .collect::<Result<_, _>>()?,
+            target,
+            stopwords: self.stopwords.iter().map(|w| w.to_lowercase()).collect(),
+            paths: self
+                .paths
+                .iter()
+                .map(|r| compile(r))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("invalid regex {:?}: {}", pattern, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::rules::Found;
+
+    const CONFIG: &str = r#"
+title = "Team rules"
+
+[extend]
+useDefault = true
+disabledRules = ["generic-api-key"]
+
+[[rules]]
+id = "acme-internal-token"
+description = "Acme internal service token"
+regex = '''\b(acme_[a-z0-9]{24})\b'''
+secretGroup = 1
+entropy = 3.0
+keywords = ["ACME_"]
+tags = ["internal"]
+
+[rules.allowlist]
+regexes = ['''^acme_0+$''']
+
+[[rules]]
+id = "pem-files"
+path = '''\.pem$'''
+
+[allowlist]
+description = "Test fixtures"
+paths = ['''^tests/fixtures/''']
+stopwords = ["dummy"]
+commits = ["abc123"]
+"#;
+
+    #[test]
+    fn test_parse_gitleaks_config() {
+        let file = parse(CONFIG).unwrap();
+        assert_eq!(file.disabled_rules, ["generic-api-key"]);
+        assert_eq!(file.rules.len(), 1, "path-only rules are skipped");
+
+        let rule = &file.rules[0];
+        assert_eq!(rule.id, "acme-internal-token");
+        assert_eq!(rule.keywords, ["acme_"]);
+        let line = "+token = acme_k3j5h2g8f7d6s5a4q1w2e3r4";
+        assert_eq!(rule.find(line, None, &file.allowlists), [(9, 38)]);
+        assert!(rule
+            .find("+acme_000000000000000000000000", None, &[])
+            .is_empty());
+
+        let fixture = Found {
+            secret: "x",
+            matched: "x",
+            line: "x",
+            path: Some("tests/fixtures/env"),
+        };
+        assert!(file.allowlists[0].allows(&fixture));
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_rule() {
+        let err = parse("[[rules]]\nid = \"broken\"\nregex = '''(unclosed'''\n").unwrap_err();
+        assert!(err.starts_with("rule broken: invalid regex"), "{err}");
+
+        let err = parse("[allowlist]\nregexTarget = \"commit\"\n").unwrap_err();
+        assert!(err.contains("unknown regexTarget"), "{err}");
+    }
+}
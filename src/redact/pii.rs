@@ -0,0 +1,535 @@
+//! Personal data (PII) detectors and stable pseudonymization.
+//!
+//! Unlike secrets, personal data is replaced with a numbered placeholder
+//! (`[EMAIL_1]`, `[PHONE_2]`, `[IP_1]`, `[CARD_1]`) rather than a blanket
+//! `[REDACTED]`: the same value always gets the same placeholder within one
+//! [`Pseudonymizer`], so the model can still tell that a fixture uses one
+//! address in three places, or two different ones.
+//!
+//! Each category is opt-in through the `[pii]` table of `.cmt.toml`. Obvious
+//! non-personal values are left alone: reserved example domains
+//! (`example.com`, `.test`), documentation and loopback addresses, and card
+//! numbers that fail the Luhn check or match no issuer prefix.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::OnceLock;
+
+/// The `[pii]` table of the config: which categories to pseudonymize.
+///
+/// Unset categories are off; a layer that sets one (`true` or `false`)
+/// overrides the layers below it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PiiConfig {
+    pub emails: Option<bool>,
+    pub phones: Option<bool>,
+    pub ip_addresses: Option<bool>,
+    /// Card numbers that pass the Luhn check and match an issuer prefix
+    pub credit_cards: Option<bool>,
+}
+
+impl PiiConfig {
+    /// Whether any category is on.
+    pub fn is_enabled(&self) -> bool {
+        [
+            self.emails,
+            self.phones,
+            self.ip_addresses,
+            self.credit_cards,
+        ]
+        .contains(&Some(true))
+    }
+}
+
+/// A category of personal data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PiiKind {
+    Email,
+    Phone,
+    IpAddress,
+    CreditCard,
+}
+
+impl PiiKind {
+    /// Placeholder label: `EMAIL`, `PHONE`, `IP`, `CARD`.
+    fn label(self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::Phone => "PHONE",
+            PiiKind::IpAddress => "IP",
+            PiiKind::CreditCard => "CARD",
+        }
+    }
+
+    /// Equal values under this normalization share a placeholder.
+    fn normalize(self, value: &str) -> String {
+        match self {
+            PiiKind::Email | PiiKind::IpAddress => value.to_lowercase(),
+            PiiKind::Phone => value
+                .chars()
+                .filter(|c| c.is_ascii_digit() || *c == '+')
+                .collect(),
+            PiiKind::CreditCard => value.chars().filter(char::is_ascii_digit).collect(),
+        }
+    }
+}
+
+/// Replaces personal data with stable placeholders. Keep one per diff (and
+/// the commit messages that go with it) so placeholders stay consistent.
+#[derive(Debug, Clone, Default)]
+pub struct Pseudonymizer {
+    config: PiiConfig,
+    placeholders: HashMap<(PiiKind, String), String>,
+    counts: HashMap<PiiKind, usize>,
+}
+
+impl Pseudonymizer {
+    pub fn new(config: &PiiConfig) -> Self {
+        Self {
+            config: config.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Number of distinct values replaced so far.
+    pub fn len(&self) -> usize {
+        self.placeholders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.placeholders.is_empty()
+    }
+
+    /// Pseudonymize `input` (a diff, or any text). Diff headers are left
+    /// alone, as is the `+`/`-`/space marker of each diff line.
+    pub fn scrub(&mut self, input: &str) -> String {
+        if !self.config.is_enabled() {
+            return input.to_string();
+        }
+        let mut out = String::with_capacity(input.len());
+        let mut in_diff = false;
+        let mut in_manifest = false;
+        for line in input.split_inclusive('\n') {
+            if is_diff_header(line) {
+                in_diff = true;
+                if let Some((_, path)) = line
+                    .strip_prefix("diff --git ")
+                    .and_then(|l| l.rsplit_once(" b/"))
+                {
+                    in_manifest = is_manifest(path.trim_end());
+                }
+                out.push_str(line);
+                continue;
+            }
+            // Inside a diff the first column is the line marker, so an added
+            // `+4155552671` is not a phone number with a country code.
+            let prefix = if in_diff && line.starts_with(['+', '-', ' ']) {
+                1
+            } else {
+                0
+            };
+            out.push_str(&line[..prefix]);
+            out.push_str(&self.scrub_line(&line[prefix..], in_manifest));
+        }
+        out
+    }
+
+    fn scrub_line(&mut self, line: &str, in_manifest: bool) -> String {
+        let spans = find(&self.config, line, in_manifest);
+        if spans.is_empty() {
+            return line.to_string();
+        }
+        let mut out = String::with_capacity(line.len());
+        let mut last = 0;
+        for (start, end, kind) in spans {
+            out.push_str(&line[last..start]);
+            out.push_str(&self.placeholder(kind, &line[start..end]));
+            last = end;
+        }
+        out.push_str(&line[last..]);
+        out
+    }
+
+    fn placeholder(&mut self, kind: PiiKind, value: &str) -> String {
+        let counts = &mut self.counts;
+        self.placeholders
+            .entry((kind, kind.normalize(value)))
+            .or_insert_with(|| {
+                let n = counts.entry(kind).or_default();
+                *n += 1;
+                format!("[{}_{}]", kind.label(), n)
+            })
+            .clone()
+    }
+}
+
+/// Lines `git diff` writes around the changes, which hold paths, not data.
+fn is_diff_header(line: &str) -> bool {
+    [
+        "diff --git ",
+        "index ",
+        "--- a/",
+        "+++ b/",
+        "--- /dev/null",
+        "+++ /dev/null",
+        "@@ ",
+    ]
+    .iter()
+    .any(|header| line.starts_with(header))
+}
+
+/// Dependency manifests, whose four-part numbers are versions, not addresses.
+fn is_manifest(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    [
+        "Cargo.toml",
+        "Cargo.lock",
+        "package.json",
+        "package-lock.json",
+        "pom.xml",
+    ]
+    .contains(&name)
+        || [".csproj", ".vbproj", ".fsproj", ".nuspec"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+/// Byte ranges of personal data on `line`, in order and without overlaps.
+/// Categories are tried emails, cards, IPs, phones: the first to claim
+/// overlapping text names it. IP addresses aren't looked for in manifests.
+fn find(config: &PiiConfig, line: &str, in_manifest: bool) -> Vec<(usize, usize, PiiKind)> {
+    let mut spans: Vec<(usize, usize, PiiKind)> = Vec::new();
+    let mut add = |(start, end): (usize, usize), kind: PiiKind| {
+        if !spans.iter().any(|&(s, e, _)| start < e && s < end) {
+            spans.push((start, end, kind));
+        }
+    };
+    if config.emails == Some(true) {
+        emails(line)
+            .into_iter()
+            .for_each(|s| add(s, PiiKind::Email));
+    }
+    if config.credit_cards == Some(true) {
+        cards(line)
+            .into_iter()
+            .for_each(|s| add(s, PiiKind::CreditCard));
+    }
+    if config.ip_addresses == Some(true) && !in_manifest {
+        ip_addresses(line)
+            .into_iter()
+            .for_each(|s| add(s, PiiKind::IpAddress));
+    }
+    if config.phones == Some(true) {
+        phones(line)
+            .into_iter()
+            .for_each(|s| add(s, PiiKind::Phone));
+    }
+    spans.sort_unstable_by_key(|&(start, _, _)| start);
+    spans
+}
+
+fn compiled(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid PII regex"))
+}
+
+/// Whether `line[start..end]` stands on its own rather than being part of a
+/// longer number, version or identifier.
+fn is_bounded(line: &str, start: usize, end: usize) -> bool {
+    let before = line[..start].chars().next_back();
+    let mut after = line[end..].chars();
+    let next = after.next();
+    let joined = |c: char| c.is_alphanumeric() || c == '_';
+    if before.is_some_and(|c| joined(c) || c == '.') {
+        return false;
+    }
+    match next {
+        Some(c) if joined(c) => false,
+        // `1.2.3.4.5` or a sentence ending right after the value
+        Some('.' | '-') => !after.next().is_some_and(|c| c.is_ascii_digit()),
+        _ => true,
+    }
+}
+
+fn emails(line: &str) -> Vec<(usize, usize)> {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    let email = compiled(
+        &EMAIL,
+        r"(?i)\b[a-z0-9][a-z0-9._%+-]*@((?:[a-z0-9-]+\.)+([a-z]{2,24}))\b",
+    );
+    email
+        .captures_iter(line)
+        .filter(|caps| {
+            let domain = caps[1].to_lowercase();
+            let tld = caps[2].to_lowercase();
+            !is_reserved_domain(&domain) && !is_file_extension(&tld)
+        })
+        .map(|caps| {
+            let m = caps.get(0).expect("whole match");
+            (m.start(), m.end())
+        })
+        .collect()
+}
+
+/// RFC 2606 / 6761 names reserved for examples and tests.
+fn is_reserved_domain(domain: &str) -> bool {
+    ["example.com", "example.org", "example.net"]
+        .iter()
+        .any(|reserved| domain == *reserved || domain.ends_with(&format!(".{reserved}")))
+        || [".test", ".example", ".invalid", ".localhost", ".local"]
+            .iter()
+            .any(|tld| domain.ends_with(tld))
+}
+
+/// `icon@2x.png` is an image, not an address.
+fn is_file_extension(tld: &str) -> bool {
+    ["png", "jpg", "jpeg", "gif", "svg", "webp", "avif", "ico"].contains(&tld)
+}
+
+fn phones(line: &str) -> Vec<(usize, usize)> {
+    static INTERNATIONAL: OnceLock<Regex> = OnceLock::new();
+    static NATIONAL: OnceLock<Regex> = OnceLock::new();
+    // +44 20 7946 0958, +1-415-555-2671, +14155552671
+    let international = compiled(
+        &INTERNATIONAL,
+        r"\+[1-9]\d{0,3}(?:[ .-]?\(?\d{1,4}\)?){1,5}[ .-]?\d{2,6}",
+    );
+    // (415) 555-2671, 415-555-2671, 415.555.2671
+    let national = compiled(&NATIONAL, r"(?:\(\d{3}\) ?|\b\d{3}[ .-])\d{3}[ .-]\d{4}\b");
+
+    let mut spans = Vec::new();
+    for m in international
+        .find_iter(line)
+        .chain(national.find_iter(line))
+    {
+        let digits = m.as_str().chars().filter(char::is_ascii_digit).count();
+        let start = m.start();
+        if (8..=15).contains(&digits)
+            && is_bounded(line, start, m.end())
+            && !spans.iter().any(|&(s, e)| start < e && s < m.end())
+        {
+            spans.push((start, m.end()));
+        }
+    }
+    spans
+}
+
+fn ip_addresses(line: &str) -> Vec<(usize, usize)> {
+    static V4: OnceLock<Regex> = OnceLock::new();
+    static V6: OnceLock<Regex> = OnceLock::new();
+    let v4 = compiled(&V4, r"\b(?:\d{1,3}\.){3}\d{1,3}\b");
+    let v6 = compiled(&V6, r"(?i)[0-9a-f]{0,4}(?::[0-9a-f]{0,4}){2,7}");
+
+    let mut spans = Vec::new();
+    for m in v4.find_iter(line) {
+        let personal = m.as_str().parse::<Ipv4Addr>().is_ok_and(|ip| {
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_broadcast() || ip.is_documentation())
+        });
+        if personal && is_bounded(line, m.start(), m.end()) && !follows_version_key(line, m.start())
+        {
+            spans.push((m.start(), m.end()));
+        }
+    }
+    for m in v6.find_iter(line) {
+        let text = m.as_str();
+        // Code like `a::b` parses as IPv6; real addresses carry digits.
+        let personal = text.chars().any(|c| c.is_ascii_digit())
+            && text.parse::<Ipv6Addr>().is_ok_and(|ip| {
+                let documentation = ip.segments()[..2] == [0x2001, 0x0db8];
+                !(ip.is_loopback() || ip.is_unspecified() || documentation)
+            });
+        let before = line[..m.start()].chars().next_back();
+        if personal && !before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == ':') {
+            spans.push((m.start(), m.end()));
+        }
+    }
+    spans
+}
+
+/// `version 1.2.3.4`, `version = "1.2.3.4"`, `<FileVersion>1.2.3.4`,
+/// `AssemblyVersion("1.2.3.4")`, `__version__ = '1.2.3.4'`: the value is
+/// labelled as a version.
+fn follows_version_key(line: &str, start: usize) -> bool {
+    let before = line[..start].trim_end_matches([' ', '\t', '"', '\'', '=', ':', '(', '>']);
+    let key = &before[before
+        .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+        .len()..];
+    let key = key.trim_matches('_').to_lowercase();
+    key.ends_with("version") || key == "ver" || key == "v"
+}
+
+fn cards(line: &str) -> Vec<(usize, usize)> {
+    static CARD: OnceLock<Regex> = OnceLock::new();
+    let card = compiled(&CARD, r"\b\d(?:[ -]?\d){12,18}\b");
+    card.find_iter(line)
+        .filter(|m| {
+            let digits: Vec<u32> = m.as_str().chars().filter_map(|c| c.to_digit(10)).collect();
+            is_bounded(line, m.start(), m.end()) && has_issuer_prefix(&digits) && luhn(&digits)
+        })
+        .map(|m| (m.start(), m.end()))
+        .collect()
+}
+
+/// Issuer prefixes and lengths of the major card networks (Visa, Mastercard,
+/// Amex, Discover, JCB, Diners), which rules out timestamps and ids that
+/// happen to pass the Luhn check.
+fn has_issuer_prefix(digits: &[u32]) -> bool {
+    let prefix = |n: usize| digits.iter().take(n).fold(0, |acc, d| acc * 10 + d);
+    let len = digits.len();
+    match digits.first() {
+        Some(4) => matches!(len, 13 | 16 | 19),
+        Some(5) => len == 16 && (51..=55).contains(&prefix(2)),
+        Some(2) => len == 16 && (2221..=2720).contains(&prefix(4)),
+        Some(3) => match prefix(2) {
+            34 | 37 => len == 15,
+            35 => (16..=19).contains(&len),
+            30 | 36 | 38 => len == 14,
+            _ => false,
+        },
+        Some(6) => {
+            (16..=19).contains(&len)
+                && (prefix(4) == 6011 || prefix(2) == 65 || (644..=649).contains(&prefix(3)))
+        }
+        _ => false,
+    }
+}
+
+/// The Luhn checksum card numbers carry in their last digit.
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2 == 1, d * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> PiiConfig {
+        PiiConfig {
+            emails: Some(true),
+            phones: Some(true),
+            ip_addresses: Some(true),
+            credit_cards: Some(true),
+        }
+    }
+
+    #[test]
+    fn test_pseudonymizes_each_category() {
+        let mut pii = Pseudonymizer::new(&all());
+        let out = pii.scrub(
+            "owner = \"jane.doe@acme-corp.com\"\n\
+             call +44 20 7946 0958 or (415) 555-2671\n\
+             server 10.12.0.7 and fe80::1ff:fe23:4567:890a\n\
+             card 4111 1111 1111 1111, amex 378282246310005\n",
+        );
+        assert_eq!(
+            out,
+            "owner = \"[EMAIL_1]\"\n\
+             call [PHONE_1] or [PHONE_2]\n\
+             server [IP_1] and [IP_2]\n\
+             card [CARD_1], amex [CARD_2]\n"
+        );
+        assert_eq!(pii.len(), 7);
+    }
+
+    #[test]
+    fn test_same_value_same_placeholder() {
+        let mut pii = Pseudonymizer::new(&all());
+        let diff = "\
+diff --git a/fixtures/users.json b/fixtures/users.json
+--- a/fixtures/users.json
++++ b/fixtures/users.json
+@@ -1,2 +1,3 @@
+-  {\"email\": \"Bob@Shop.io\"},
++  {\"email\": \"bob@shop.io\"},
++  {\"email\": \"alice@shop.io\", \"manager\": \"bob@shop.io\"},
+";
+        let out = pii.scrub(diff);
+        assert!(out.starts_with("diff --git a/fixtures/users.json"));
+        assert!(out.contains("-  {\"email\": \"[EMAIL_1]\"},"));
+        assert!(out.contains("+  {\"email\": \"[EMAIL_1]\"},"));
+        assert!(out.contains("\"[EMAIL_2]\", \"manager\": \"[EMAIL_1]\""));
+
+        // Across calls too, so squashed commit messages agree with the diff
+        assert_eq!(
+            pii.scrub("Reported by bob@shop.io"),
+            "Reported by [EMAIL_1]"
+        );
+        assert_eq!(pii.len(), 2);
+    }
+
+    #[test]
+    fn test_leaves_non_personal_values() {
+        let mut pii = Pseudonymizer::new(&all());
+        let ordinary = "\
++version = \"2.1.0\"; oid = \"1.3.6.1.4.1\"
++bind = \"127.0.0.1:8080\" # or 0.0.0.0, docs use 192.0.2.10
++contact = \"dev@example.com\"; logo = \"logo@2x.png\"
++let ts = 1700000000000; let id = 4111111111111112;
++use std::fmt::Display; Vec::<u8>::new();
++date = 2024-01-15; build 20240115
+";
+        assert_eq!(pii.scrub(ordinary), ordinary);
+        assert!(pii.is_empty());
+    }
+
+    #[test]
+    fn test_four_part_versions_are_not_addresses() {
+        let mut pii = Pseudonymizer::new(&all());
+        let versions = "\
+diff --git a/src/build.rs b/src/build.rs
++SDK_VERSION = \"10.0.190.1\"
++__version__ = '1.2.3.4'
++release version 2.3.4.5, upgraded from v1.2.3.4
++[assembly: AssemblyVersion(\"4.1.0.2\")]
+diff --git a/App/App.csproj b/App/App.csproj
++    <PackageReference Include=\"Serilog\" Version=\"3.1.1.0\" />
+diff --git a/package.json b/package.json
++    \"dep\": \"8.8.4.4\",
+";
+        assert_eq!(pii.scrub(versions), versions);
+        assert!(pii.is_empty());
+
+        // Addresses elsewhere in the same diff are still replaced.
+        assert_eq!(
+            pii.scrub("diff --git a/hosts b/hosts\n+server = 10.12.0.7\n"),
+            "diff --git a/hosts b/hosts\n+server = [IP_1]\n"
+        );
+    }
+
+    #[test]
+    fn test_categories_are_independent() {
+        let mut pii = Pseudonymizer::new(&PiiConfig {
+            emails: Some(true),
+            ..PiiConfig::default()
+        });
+        assert_eq!(
+            pii.scrub("+ops@acme.io 10.0.0.1 +1 415 555 2671"),
+            "+[EMAIL_1] 10.0.0.1 +1 415 555 2671"
+        );
+
+        let mut off = Pseudonymizer::new(&PiiConfig::default());
+        assert_eq!(off.scrub("ops@acme.io"), "ops@acme.io");
+    }
+
+    #[test]
+    fn test_luhn_and_issuer_prefix() {
+        let digits = |s: &str| s.chars().filter_map(|c| c.to_digit(10)).collect::<Vec<_>>();
+        assert!(luhn(&digits("4111111111111111")));
+        assert!(!luhn(&digits("4111111111111112")));
+        assert!(has_issuer_prefix(&digits("5555555555554444")));
+        assert!(has_issuer_prefix(&digits("6011111111111117")));
+        assert!(!has_issuer_prefix(&digits("1700000000000")));
+    }
+}
@@ -0,0 +1,242 @@
+//! Reversible placeholders for identifiers that are sensitive but not secret.
+//!
+//! Internal hostnames, customer names and the like are useful in a commit
+//! message but shouldn't reach a third-party model. Each configured value is
+//! sent as a token such as `<<HOST_1>>`, and the tokens in the model's answer
+//! are replaced with the original values locally, so the message is accurate
+//! while the provider only ever saw the tokens.
+//!
+//! ```toml
+//! [placeholders.host]
+//! patterns = ['[a-z0-9-]+\.corp\.internal']
+//!
+//! [placeholders.customer]
+//! values = ["Acme Corp", "Globex"]
+//! ```
+//!
+//! `values` are literal names, matched case-insensitively on word boundaries;
+//! `patterns` are regular expressions. The table key, uppercased, labels the
+//! token.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+/// One `[placeholders.<label>]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaceholderRule {
+    /// Literal values, matched case-insensitively as whole words
+    pub values: Vec<String>,
+    /// Regular expressions
+    pub patterns: Vec<String>,
+}
+
+/// The compiled `[placeholders]` rules.
+#[derive(Debug, Clone, Default)]
+pub struct Placeholders {
+    rules: Vec<(String, Vec<Regex>)>,
+}
+
+impl Placeholders {
+    /// Compile the rules. Errors name the label and the value at fault.
+    pub fn new(config: &BTreeMap<String, PlaceholderRule>) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (label, rule) in config {
+            if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "placeholders: label {:?} may only use letters, digits and '_'",
+                    label
+                ));
+            }
+            let context = |e: regex::Error| format!("placeholders.{}: {}", label, e);
+            let mut regexes = Vec::new();
+            for value in rule.values.iter().filter(|value| !value.trim().is_empty()) {
+                regexes.push(Regex::new(&literal_pattern(value.trim())).map_err(context)?);
+            }
+            for pattern in &rule.patterns {
+                regexes.push(Regex::new(pattern).map_err(context)?);
+            }
+            if !regexes.is_empty() {
+                rules.push((label.to_uppercase(), regexes));
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Replace every configured identifier in `text` with its token,
+    /// recording the mapping in `map` so it can be undone.
+    pub fn tokenize(&self, map: &mut PlaceholderMap, text: &str) -> String {
+        let mut spans: Vec<(usize, usize, &str)> = self
+            .rules
+            .iter()
+            .flat_map(|(label, regexes)| {
+                regexes.iter().flat_map(move |regex| {
+                    regex
+                        .find_iter(text)
+                        .filter(|m| !m.is_empty())
+                        .map(move |m| (m.start(), m.end(), label.as_str()))
+                })
+            })
+            .collect();
+        // Leftmost first, and the longest of matches starting together, so
+        // `db.corp.internal` isn't split by a shorter `corp` value.
+        spans.sort_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)));
+
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, end, label) in spans {
+            if start < last {
+                continue;
+            }
+            out.push_str(&text[last..start]);
+            out.push_str(&map.token(label, &text[start..end]));
+            last = end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+}
+
+/// `value` as a case-insensitive regex, anchored on word boundaries where
+/// it starts or ends with a word character.
+fn literal_pattern(value: &str) -> String {
+    let boundary = |c: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    };
+    format!(
+        "(?i){}{}{}",
+        boundary(value.chars().next()),
+        regex::escape(value),
+        boundary(value.chars().next_back())
+    )
+}
+
+/// Tokens handed out for one prompt and the values they stand for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaceholderMap {
+    /// Token (`<<HOST_1>>`) to original value
+    originals: HashMap<String, String>,
+    /// (label, original value) to token
+    tokens: HashMap<(String, String), String>,
+    counts: HashMap<String, usize>,
+}
+
+impl PlaceholderMap {
+    /// Number of distinct values replaced.
+    pub fn len(&self) -> usize {
+        self.originals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.originals.is_empty()
+    }
+
+    fn token(&mut self, label: &str, value: &str) -> String {
+        let key = (label.to_string(), value.to_string());
+        if let Some(token) = self.tokens.get(&key) {
+            return token.clone();
+        }
+        let n = self.counts.entry(label.to_string()).or_default();
+        *n += 1;
+        let token = format!("<<{}_{}>>", label, n);
+        self.originals.insert(token.clone(), value.to_string());
+        self.tokens.insert(key, token.clone());
+        token
+    }
+
+    /// Put the original values back in place of the tokens in `text`.
+    /// Tokens the model altered in case are recognised; unknown tokens are
+    /// left as they are.
+    pub fn restore(&self, text: &str) -> String {
+        static TOKEN: OnceLock<Regex> = OnceLock::new();
+        if self.is_empty() {
+            return text.to_string();
+        }
+        let token =
+            TOKEN.get_or_init(|| Regex::new(r"<<[A-Za-z0-9_]+_\d+>>").expect("valid token regex"));
+        token
+            .replace_all(text, |caps: &regex::Captures| {
+                let found = &caps[0];
+                self.originals
+                    .get(&found.to_uppercase())
+                    .cloned()
+                    .unwrap_or_else(|| found.to_string())
+            })
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders() -> Placeholders {
+        let config = BTreeMap::from([
+            (
+                "host".to_string(),
+                PlaceholderRule {
+                    patterns: vec![r"[a-z0-9-]+\.corp\.internal".to_string()],
+                    ..PlaceholderRule::default()
+                },
+            ),
+            (
+                "customer".to_string(),
+                PlaceholderRule {
+                    values: vec!["Acme Corp".to_string(), "Globex".to_string()],
+                    ..PlaceholderRule::default()
+                },
+            ),
+        ]);
+        Placeholders::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_tokenize_and_restore_round_trip() {
+        let placeholders = placeholders();
+        let mut map = PlaceholderMap::default();
+        let text = "+// Acme Corp asked for a longer timeout on db-1.corp.internal\n\
+                    +const HOSTS = [\"db-1.corp.internal\", \"cache.corp.internal\"]; // GLOBEX too\n";
+        let tokenized = placeholders.tokenize(&mut map, text);
+        assert_eq!(
+            tokenized,
+            "+// <<CUSTOMER_1>> asked for a longer timeout on <<HOST_1>>\n\
+             +const HOSTS = [\"<<HOST_1>>\", \"<<HOST_2>>\"]; // <<CUSTOMER_2>> too\n"
+        );
+        assert_eq!(map.len(), 4);
+
+        // Values only match whole words
+        assert_eq!(placeholders.tokenize(&mut map, "Globexian"), "Globexian");
+
+        let message = "fix: raise timeout on <<HOST_1>> for <<customer_1>>\n\n\
+                       - Keep <<HOST_2>> as is; <<HOST_9>> is unknown";
+        assert_eq!(
+            map.restore(message),
+            "fix: raise timeout on db-1.corp.internal for Acme Corp\n\n\
+             - Keep cache.corp.internal as is; <<HOST_9>> is unknown"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules_are_config_errors() {
+        let bad_regex = BTreeMap::from([(
+            "host".to_string(),
+            PlaceholderRule {
+                patterns: vec!["(unclosed".to_string()],
+                ..PlaceholderRule::default()
+            },
+        )]);
+        let err = Placeholders::new(&bad_regex).unwrap_err();
+        assert!(err.starts_with("placeholders.host:"), "{err}");
+
+        let bad_label = BTreeMap::from([("internal host".to_string(), PlaceholderRule::default())]);
+        assert!(Placeholders::new(&bad_label).is_err());
+        assert!(Placeholders::new(&BTreeMap::new()).unwrap().is_empty());
+    }
+}
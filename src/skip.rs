@@ -0,0 +1,319 @@
+//! Rules for files that are left out of the diff automatically.
+//!
+//! The built-in rules cover lockfiles, minified and source-map assets, images,
+//! `dist/` and `build/` output, and binary content. They can be switched off
+//! (all of them, or by name) and extended with rules in the `[skip]` table of
+//! `.cmt.toml`:
+//!
+//! ```toml
+//! [skip]
+//! disable = ["image"]
+//!
+//! [[skip.rules]]
+//! name = "protobuf"
+//! globs = ["**/*.pb.go"]
+//!
+//! [[skip.rules]]
+//! name = "large-data"
+//! extensions = ["csv", "jsonl"]
+//! min_bytes = 100000
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::cmtignore::matches_pattern;
+
+/// Bytes inspected when sniffing for binary content (git looks at 8000 too).
+const SNIFF_BYTES: usize = 8000;
+
+/// Leading bytes of binary formats that may not contain a NUL early on.
+const BINARY_MAGIC: &[&[u8]] = &[
+    b"\x89PNG",
+    b"GIF87a",
+    b"GIF89a",
+    b"\xFF\xD8\xFF",
+    b"%PDF-",
+    b"PK\x03\x04",
+    b"\x7FELF",
+    b"\x00asm",
+    b"\x1F\x8B",
+    b"RIFF",
+    b"OggS",
+    b"ID3",
+];
+
+/// The `[skip]` table of the config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkipConfig {
+    /// Apply the built-in rules
+    pub builtin: bool,
+    /// Built-in rules to turn off, by name
+    pub disable: Vec<String>,
+    /// Additional rules, checked after the built-in ones
+    pub rules: Vec<SkipRule>,
+}
+
+impl Default for SkipConfig {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            disable: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// One skip rule. A file matches when every criterion the rule sets holds:
+/// its path matches one of `globs` or `extensions` (if any are given), it is
+/// at least `min_bytes` large (if set) and its content is binary (if
+/// `binary`). Matching is case-insensitive.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkipRule {
+    /// Shown as the reason a file was skipped, and used by `disable`
+    pub name: String,
+    /// `.cmtignore`-style globs; one without a `/` matches the file name at
+    /// any depth
+    pub globs: Vec<String>,
+    /// File name suffixes without the leading dot; may be compound (`min.js`)
+    pub extensions: Vec<String>,
+    /// Size threshold in bytes (the larger of the old and new version)
+    pub min_bytes: Option<u64>,
+    /// Match files whose content looks binary
+    pub binary: bool,
+}
+
+impl SkipRule {
+    fn new(name: &str, globs: &[&str], extensions: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            globs: globs.iter().map(|g| g.to_string()).collect(),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// What about `file` this rule matched, or `None`.
+    fn matches(&self, file: &SkipCandidate) -> Option<String> {
+        let has_path_criteria = !self.globs.is_empty() || !self.extensions.is_empty();
+        if !has_path_criteria && self.min_bytes.is_none() && !self.binary {
+            return None;
+        }
+
+        let path = file.path.to_lowercase();
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        let path_match = if has_path_criteria {
+            let glob = self.globs.iter().find(|glob| {
+                let glob = glob.to_lowercase();
+                if glob.contains('/') {
+                    matches_pattern(&path, &glob)
+                } else {
+                    matches_pattern(name, &glob)
+                }
+            });
+            let extension = self.extensions.iter().find(|ext| {
+                let ext = ext.trim_start_matches('.').to_lowercase();
+                name.len() > ext.len() + 1 && name.ends_with(&format!(".{}", ext))
+            });
+            match (glob, extension) {
+                (Some(glob), _) => Some(glob.clone()),
+                (None, Some(ext)) => Some(format!("*.{}", ext.trim_start_matches('.'))),
+                (None, None) => return None,
+            }
+        } else {
+            None
+        };
+
+        if let Some(min_bytes) = self.min_bytes {
+            if file.size < min_bytes {
+                return None;
+            }
+        }
+        if self.binary && !(file.is_binary)() {
+            return None;
+        }
+
+        Some(match (path_match, self.min_bytes) {
+            (Some(path_match), _) => path_match,
+            (None, Some(min_bytes)) => format!("{} bytes or more", min_bytes),
+            (None, None) => "binary content".to_string(),
+        })
+    }
+}
+
+/// The built-in rules, in the order they are checked.
+pub fn builtin_rules() -> Vec<SkipRule> {
+    vec![
+        SkipRule::new(
+            "lockfile",
+            &["package-lock.json", "npm-shrinkwrap.json", "pnpm-lock.yaml"],
+            &["lock"],
+        ),
+        SkipRule::new("generated-asset", &[], &["map", "min.js", "min.css"]),
+        SkipRule::new(
+            "image",
+            &[],
+            &[
+                "png", "jpg", "jpeg", "gif", "webp", "avif", "bmp", "ico", "svg",
+            ],
+        ),
+        SkipRule::new("build-output", &["dist/**", "build/**"], &[]),
+        SkipRule {
+            name: "binary".to_string(),
+            binary: true,
+            ..SkipRule::default()
+        },
+    ]
+}
+
+/// A changed file as seen by the rules. Content is only sniffed when a rule
+/// asks whether the file is binary.
+pub struct SkipCandidate<'a> {
+    pub path: &'a str,
+    pub size: u64,
+    pub is_binary: &'a dyn Fn() -> bool,
+}
+
+/// The active rules: the enabled built-ins followed by the configured ones.
+#[derive(Debug, Clone)]
+pub struct SkipRules {
+    rules: Vec<SkipRule>,
+}
+
+impl SkipRules {
+    pub fn new(config: &SkipConfig) -> Self {
+        let mut rules: Vec<SkipRule> = if config.builtin {
+            builtin_rules()
+                .into_iter()
+                .filter(|rule| !config.disable.contains(&rule.name))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        rules.extend(config.rules.iter().cloned());
+        Self { rules }
+    }
+
+    /// Why `file` is skipped, naming the rule and what it matched (e.g.
+    /// `skip rule lockfile: *.lock`), if any rule applies.
+    pub fn reason(&self, file: &SkipCandidate) -> Option<String> {
+        self.rules.iter().find_map(|rule| {
+            rule.matches(file)
+                .map(|matched| format!("skip rule {}: {}", rule.name, matched))
+        })
+    }
+}
+
+/// Whether `bytes` look like binary content: a NUL byte early on, or the
+/// signature of a common binary format.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    head.contains(&0) || BINARY_MAGIC.iter().any(|magic| head.starts_with(magic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(rules: &SkipRules, path: &str, size: u64, binary: bool) -> Option<String> {
+        rules.reason(&SkipCandidate {
+            path,
+            size,
+            is_binary: &|| binary,
+        })
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        let rules = SkipRules::new(&SkipConfig::default());
+        assert_eq!(
+            reason(&rules, "Cargo.lock", 10, false).as_deref(),
+            Some("skip rule lockfile: *.lock")
+        );
+        assert_eq!(
+            reason(&rules, "web/package-lock.json", 10, false).as_deref(),
+            Some("skip rule lockfile: package-lock.json")
+        );
+        // Compound extensions match (Path::extension would only see `js`)
+        assert_eq!(
+            reason(&rules, "static/app.min.js", 10, false).as_deref(),
+            Some("skip rule generated-asset: *.min.js")
+        );
+        assert_eq!(reason(&rules, "src/app.js", 10, false), None);
+        assert_eq!(
+            reason(&rules, "assets/Logo.PNG", 10, false).as_deref(),
+            Some("skip rule image: *.png")
+        );
+        assert_eq!(
+            reason(&rules, "dist/bundle.js", 10, false).as_deref(),
+            Some("skip rule build-output: dist/**")
+        );
+        assert_eq!(reason(&rules, "src/dist/mod.rs", 10, false), None);
+        assert_eq!(
+            reason(&rules, "data/model.bin", 10, true).as_deref(),
+            Some("skip rule binary: binary content")
+        );
+    }
+
+    #[test]
+    fn test_disable_and_extend() {
+        let config = SkipConfig {
+            disable: vec!["image".to_string()],
+            rules: vec![
+                SkipRule::new("protobuf", &["**/*.pb.go"], &[]),
+                SkipRule {
+                    name: "large-data".to_string(),
+                    extensions: vec!["csv".to_string()],
+                    min_bytes: Some(1000),
+                    ..SkipRule::default()
+                },
+            ],
+            ..SkipConfig::default()
+        };
+        let rules = SkipRules::new(&config);
+        assert_eq!(reason(&rules, "logo.png", 10, false), None);
+        assert_eq!(
+            reason(&rules, "api/v1/service.pb.go", 10, false).as_deref(),
+            Some("skip rule protobuf: **/*.pb.go")
+        );
+        assert_eq!(reason(&rules, "data/small.csv", 999, false), None);
+        assert_eq!(
+            reason(&rules, "data/big.csv", 1000, false).as_deref(),
+            Some("skip rule large-data: *.csv")
+        );
+
+        let none = SkipRules::new(&SkipConfig {
+            builtin: false,
+            ..SkipConfig::default()
+        });
+        assert_eq!(reason(&none, "Cargo.lock", 10, true), None);
+    }
+
+    #[test]
+    fn test_size_only_rule() {
+        let rules = SkipRules::new(&SkipConfig {
+            builtin: false,
+            rules: vec![SkipRule {
+                name: "huge".to_string(),
+                min_bytes: Some(1_000_000),
+                ..SkipRule::default()
+            }],
+            ..SkipConfig::default()
+        });
+        assert_eq!(
+            reason(&rules, "src/schema.rs", 2_000_000, false).as_deref(),
+            Some("skip rule huge: 1000000 bytes or more")
+        );
+        assert_eq!(reason(&rules, "src/schema.rs", 10, false), None);
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(looks_binary(b"\x89PNG\r\n\x1a\n"));
+        assert!(looks_binary(b"text\0more"));
+        assert!(looks_binary(b"%PDF-1.7\n"));
+        assert!(!looks_binary(b"fn main() {}\n"));
+        assert!(!looks_binary(b""));
+    }
+}
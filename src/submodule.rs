@@ -0,0 +1,247 @@
+//! Upstream commits behind a submodule pointer bump.
+//!
+//! A staged gitlink change is just two SHAs in the diff (`Subproject commit
+//! ...`). When the submodule is checked out and has both commits, its history
+//! between them is read so the prompt can say what the update brings in.
+
+use git2::{DescribeFormatOptions, DescribeOptions, Oid, Repository, Sort};
+use serde::Serialize;
+use std::fmt;
+
+/// Commit subjects collected per update before the rest are only counted.
+const MAX_SUBMODULE_COMMITS: usize = 20;
+
+/// Characters of a SHA shown for a submodule commit.
+const SHORT_SHA_LEN: usize = 7;
+
+/// A submodule whose pinned commit changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SubmoduleUpdate {
+    pub path: String,
+    /// Short SHA pinned before (`None` when the submodule was added)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_commit: Option<String>,
+    /// Short SHA pinned now (`None` when the submodule was removed)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_commit: Option<String>,
+    /// Tag pointing exactly at the old commit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_tag: Option<String>,
+    /// Tag pointing exactly at the new commit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_tag: Option<String>,
+    /// Subjects of the commits brought in, newest first (capped)
+    pub commits: Vec<String>,
+    /// Commits brought in (all of them, not just the listed subjects)
+    pub commit_count: usize,
+    /// Commits of the old pin no longer included (a rollback or rebase)
+    pub removed_count: usize,
+    /// Whether the submodule's history could be read at all
+    pub history_available: bool,
+}
+
+impl SubmoduleUpdate {
+    /// Describe the gitlink change at `path` from `old` to `new` (zero ids for
+    /// an added or removed submodule), reading its history when possible.
+    pub fn describe(repo: &Repository, path: &str, old: Oid, new: Oid) -> Self {
+        let pin = |oid: Oid| (!oid.is_zero()).then(|| short_sha(oid));
+        let mut update = Self {
+            path: path.to_string(),
+            old_commit: pin(old),
+            new_commit: pin(new),
+            ..Self::default()
+        };
+        if let Some(sub) = open(repo, path) {
+            update.read_history(&sub, old, new);
+        }
+        update
+    }
+
+    fn read_history(&mut self, sub: &Repository, old: Oid, new: Oid) {
+        let has = |oid: Oid| oid.is_zero() || sub.find_commit(oid).is_ok();
+        if !has(old) || !has(new) {
+            return;
+        }
+        self.history_available = true;
+        self.old_tag = exact_tag(sub, old);
+        self.new_tag = exact_tag(sub, new);
+        if new.is_zero() || old.is_zero() {
+            return;
+        }
+        if let Ok((commits, count)) = walk(sub, new, old) {
+            self.commits = commits;
+            self.commit_count = count;
+        }
+        if let Ok((_, removed)) = walk(sub, old, new) {
+            self.removed_count = removed;
+        }
+    }
+}
+
+impl fmt::Display for SubmoduleUpdate {
+    /// `vendor/foo: 1a2b3c4 (v2.2) -> 5d6e7f8 (v2.3), 5 commits`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pin = |commit: &Option<String>, tag: &Option<String>| match (commit, tag) {
+            (Some(commit), Some(tag)) => format!("{} ({})", commit, tag),
+            (Some(commit), None) => commit.clone(),
+            (None, _) => String::new(),
+        };
+        let old = pin(&self.old_commit, &self.old_tag);
+        let new = pin(&self.new_commit, &self.new_tag);
+        match (&self.old_commit, &self.new_commit) {
+            (None, _) => write!(f, "{}: added at {}", self.path, new)?,
+            (_, None) => write!(f, "{}: removed (was {})", self.path, old)?,
+            _ => write!(f, "{}: {} -> {}", self.path, old, new)?,
+        }
+        if !self.history_available {
+            return write!(f, " (submodule history not available)");
+        }
+        if self.commit_count > 0 {
+            write!(
+                f,
+                ", {} commit{}",
+                self.commit_count,
+                if self.commit_count == 1 { "" } else { "s" }
+            )?;
+        }
+        if self.removed_count > 0 {
+            write!(f, ", {} rolled back", self.removed_count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The submodule's repository: through `.gitmodules` when registered, else a
+/// repository checked out at `path` in the work tree.
+fn open(repo: &Repository, path: &str) -> Option<Repository> {
+    if let Ok(sub) = repo.find_submodule(path).and_then(|s| s.open()) {
+        return Some(sub);
+    }
+    Repository::open(repo.workdir()?.join(path)).ok()
+}
+
+/// Subjects of commits reachable from `from` but not `hide`, newest first
+/// (capped), and how many there are in total.
+fn walk(sub: &Repository, from: Oid, hide: Oid) -> Result<(Vec<String>, usize), git2::Error> {
+    let mut revwalk = sub.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(from)?;
+    revwalk.hide(hide)?;
+
+    let mut subjects = Vec::new();
+    let mut count = 0;
+    for oid in revwalk {
+        let oid = oid?;
+        count += 1;
+        if subjects.len() < MAX_SUBMODULE_COMMITS {
+            let commit = sub.find_commit(oid)?;
+            subjects.push(commit.summary().unwrap_or_default().to_string());
+        }
+    }
+    Ok((subjects, count))
+}
+
+/// A tag pointing exactly at `oid`.
+fn exact_tag(sub: &Repository, oid: Oid) -> Option<String> {
+    if oid.is_zero() {
+        return None;
+    }
+    let commit = sub.find_commit(oid).ok()?;
+    let mut options = DescribeOptions::new();
+    options.describe_tags().max_candidates_tags(0);
+    let describe = commit.as_object().describe(&options).ok()?;
+    describe
+        .format(Some(DescribeFormatOptions::new().abbreviated_size(0)))
+        .ok()
+}
+
+fn short_sha(oid: Oid) -> String {
+    oid.to_string()[..SHORT_SHA_LEN].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(repo: &Repository, message: &str) -> Oid {
+        let sig = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let parents: Vec<git2::Commit> = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .into_iter()
+            .collect();
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_describe_lists_commits_between_pins() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let sub = Repository::init(temp_dir.path().join("vendor/foo")).unwrap();
+
+        let old = commit(&sub, "Initial import");
+        let tag = |name: &str, oid: Oid| {
+            sub.tag_lightweight(name, &sub.find_object(oid, None).unwrap(), false)
+                .unwrap();
+        };
+        tag("v2.2", old);
+        commit(&sub, "Add retry with backoff");
+        commit(&sub, "Fix parser on empty input");
+        let new = commit(&sub, "Release 2.3");
+        tag("v2.3", new);
+
+        let update = SubmoduleUpdate::describe(&repo, "vendor/foo", old, new);
+        assert!(update.history_available);
+        assert_eq!(update.old_tag.as_deref(), Some("v2.2"));
+        assert_eq!(update.new_tag.as_deref(), Some("v2.3"));
+        assert_eq!(update.commit_count, 3);
+        assert_eq!(
+            update.commits,
+            [
+                "Release 2.3",
+                "Fix parser on empty input",
+                "Add retry with backoff"
+            ]
+        );
+        assert_eq!(
+            update.to_string(),
+            format!(
+                "vendor/foo: {} (v2.2) -> {} (v2.3), 3 commits",
+                short_sha(old),
+                short_sha(new)
+            )
+        );
+
+        // Going back is a rollback, not an update
+        let back = SubmoduleUpdate::describe(&repo, "vendor/foo", new, old);
+        assert_eq!(back.commit_count, 0);
+        assert_eq!(back.removed_count, 3);
+    }
+
+    #[test]
+    fn test_describe_without_checkout() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let old = Oid::from_str("1111111111111111111111111111111111111111").unwrap();
+        let new = Oid::from_str("2222222222222222222222222222222222222222").unwrap();
+
+        let update = SubmoduleUpdate::describe(&repo, "vendor/foo", old, new);
+        assert!(!update.history_available);
+        assert_eq!(
+            update.to_string(),
+            "vendor/foo: 1111111 -> 2222222 (submodule history not available)"
+        );
+
+        let added = SubmoduleUpdate::describe(&repo, "vendor/bar", Oid::zero(), new);
+        assert_eq!(
+            added.to_string(),
+            "vendor/bar: added at 2222222 (submodule history not available)"
+        );
+    }
+}
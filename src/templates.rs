@@ -242,6 +242,17 @@ impl TemplateManager {
         &self,
         template_name: &str,
         data: &CommitTemplate,
+    ) -> Result<String, TemplateError> {
+        self.render_with_issue_footer(template_name, data, config::defaults::DEFAULT_ISSUE_FOOTER)
+    }
+
+    /// Render a template, formatting the `{{issues_footer}}` variable with
+    /// `issue_footer` (one line per reference, `{id}` replaced by each).
+    pub fn render_with_issue_footer(
+        &self,
+        template_name: &str,
+        data: &CommitTemplate,
+        issue_footer: &str,
     ) -> Result<String, TemplateError> {
         if !self.handlebars.has_template(template_name) {
             return Err(TemplateError::NotFound(format!(
@@ -250,7 +261,12 @@ impl TemplateManager {
             )));
         }
 
-        let rendered = self.handlebars.render(template_name, &json!(data))?;
+        let mut context = json!(data);
+        if let Some(issues) = &data.issues {
+            context["issues_footer"] = json!(crate::issues::format_footer(issues, issue_footer));
+        }
+
+        let rendered = self.handlebars.render(template_name, &context)?;
         Ok(normalize_message(&rendered))
     }
 
@@ -429,6 +445,35 @@ mod tests {
         assert!(!rendered.ends_with('\n'), "should be trimmed: {rendered:?}");
     }
 
+    #[test]
+    fn test_conventional_template_custom_issue_footer() {
+        let mut manager = TemplateManager {
+            handlebars: Handlebars::new(),
+            templates: HashMap::new(),
+        };
+        manager
+            .register_template(
+                "conventional",
+                &crate::config::defaults::conventional_template(),
+            )
+            .unwrap();
+
+        let data = CommitTemplate {
+            commit_type: CommitType::Fix,
+            subject: "handle expired sessions".to_string(),
+            issues: Some("PROJ-9, PROJ-10".to_string()),
+            ..Default::default()
+        };
+
+        let rendered = manager
+            .render_with_issue_footer("conventional", &data, "Closes {id}")
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "fix: handle expired sessions\n\nCloses PROJ-9\nCloses PROJ-10"
+        );
+    }
+
     #[test]
     fn test_commit_template_serialization() {
         // Create a valid CommitTemplate instance
@@ -0,0 +1,370 @@
+//! Git trailers (`Signed-off-by`, `Co-authored-by`, `Reviewed-by`, ...).
+//!
+//! Trailers are appended after template rendering, in git's trailer format: a
+//! final paragraph of `Key: value` lines. As with `git interpret-trailers`,
+//! only the last paragraph can be the trailer block, and only when every line
+//! in it is a trailer (or a continuation line). If the message already ends
+//! with one, new trailers join it; exact duplicates (case-insensitive key,
+//! same value) are dropped, both among the existing trailers and the new
+//! ones, so `--signoff` twice, or a trailer the template already wrote, never
+//! repeats.
+
+use git2::Repository;
+use std::fmt;
+
+use crate::config::Config;
+use crate::git::BranchCommit;
+
+/// A single `Key: value` trailer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+impl Trailer {
+    pub fn new(key: &str, value: &str) -> Self {
+        Self {
+            key: key.trim().to_string(),
+            value: value.trim().to_string(),
+        }
+    }
+
+    /// Parse a `Key: value` line. Keys are git trailer tokens (a letter, then
+    /// letters, digits, `-`), plus the Conventional Commits `BREAKING CHANGE`
+    /// footer. The colon must be followed by whitespace, so a URL
+    /// (`https://...`) or a time (`12:30`) isn't mistaken for a trailer.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (key, value) = line.split_once(':')?;
+        let key = key.trim_end();
+        let valid_key = key == "BREAKING CHANGE"
+            || (key.starts_with(|c: char| c.is_ascii_alphabetic())
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+        if !valid_key || !value.starts_with([' ', '\t']) || value.trim().is_empty() {
+            return None;
+        }
+        Some(Self::new(key, value))
+    }
+
+    fn same_as(&self, other: &Trailer) -> bool {
+        self.key.eq_ignore_ascii_case(&other.key) && self.value == other.value
+    }
+}
+
+impl fmt::Display for Trailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.value)
+    }
+}
+
+/// Whether `paragraph` is a trailer block: every line is a trailer, or a
+/// whitespace-indented continuation of the previous one.
+fn is_trailer_block(paragraph: &str) -> bool {
+    let mut lines = paragraph.lines();
+    match lines.next() {
+        Some(first) if Trailer::parse(first).is_some() => {}
+        _ => return false,
+    }
+    lines.all(|l| l.starts_with([' ', '\t']) || Trailer::parse(l).is_some())
+}
+
+/// Append `trailers` to `message` in git trailer format, deduplicating
+/// against trailers already present (and dropping repeats among those).
+pub fn append_trailers(message: &str, trailers: &[Trailer]) -> String {
+    let message = message.trim_end();
+    let paragraphs: Vec<&str> = message.split("\n\n").collect();
+
+    // The subject paragraph is never a trailer block ("fix: foo" looks like one).
+    let existing_block = paragraphs
+        .last()
+        .filter(|p| paragraphs.len() > 1 && is_trailer_block(p));
+
+    // Existing trailers, each with its continuation lines, kept verbatim.
+    let mut present: Vec<Trailer> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut keep = true;
+    for line in existing_block.map(|p| p.lines()).into_iter().flatten() {
+        if let Some(trailer) = Trailer::parse(line) {
+            keep = !present.iter().any(|t| t.same_as(&trailer));
+            if keep {
+                present.push(trailer);
+            }
+        }
+        if keep {
+            lines.push(line);
+        }
+    }
+    let existing_len = lines.len();
+
+    let added: Vec<String> = trailers
+        .iter()
+        .filter(|trailer| {
+            let new = !present.iter().any(|t| t.same_as(trailer));
+            if new {
+                present.push((*trailer).clone());
+            }
+            new
+        })
+        .map(Trailer::to_string)
+        .collect();
+
+    let deduped = existing_block.is_some_and(|p| p.lines().count() != existing_len);
+    if added.is_empty() && !deduped {
+        return message.to_string();
+    }
+    let body = match existing_block {
+        Some(_) => paragraphs[..paragraphs.len() - 1].join("\n\n"),
+        None => message.to_string(),
+    };
+    let block: Vec<&str> = lines
+        .into_iter()
+        .chain(added.iter().map(String::as_str))
+        .collect();
+    format!("{}\n\n{}", body, block.join("\n"))
+}
+
+/// Build the `Signed-off-by` trailer from git config `user.name`/`user.email`.
+pub fn signoff(repo: &Repository) -> Result<Trailer, git2::Error> {
+    let sig = repo.signature()?;
+    Ok(Trailer::new(
+        "Signed-off-by",
+        &format!(
+            "{} <{}>",
+            sig.name().unwrap_or_default(),
+            sig.email().unwrap_or_default()
+        ),
+    ))
+}
+
+/// Resolve a `--co-author` query to a `Co-authored-by` trailer.
+///
+/// A full `Name <email>` is used verbatim; anything else is matched
+/// (case-insensitively) against the name or email of recent authors.
+pub fn resolve_co_author(query: &str, authors: &[(String, String)]) -> Option<Trailer> {
+    let query = query.trim();
+    if query.contains('<') && query.ends_with('>') {
+        return Some(Trailer::new("Co-authored-by", query));
+    }
+    let needle = query.to_lowercase();
+    authors
+        .iter()
+        .find(|(name, email)| {
+            name.to_lowercase().contains(&needle) || email.to_lowercase().contains(&needle)
+        })
+        .map(|(name, email)| Trailer::new("Co-authored-by", &format!("{} <{}>", name, email)))
+}
+
+/// Number of commits scanned when resolving `--co-author` against history.
+const CO_AUTHOR_HISTORY: usize = 200;
+
+/// Turn the repository-dependent trailer options (`signoff`, `co_authors`)
+/// into concrete `trailers` entries on `config`, so generation only has to
+/// append static trailers.
+pub fn resolve_trailers(config: &mut Config, repo: &Repository) -> Result<(), String> {
+    if config.signoff {
+        let trailer = signoff(repo).map_err(|e| {
+            format!(
+                "--signoff needs git config user.name and user.email: {}",
+                e.message()
+            )
+        })?;
+        config.trailers.push(trailer.to_string());
+        config.signoff = false;
+    }
+
+    if !config.co_authors.is_empty() {
+        let authors = crate::git::get_recent_authors(repo, CO_AUTHOR_HISTORY)
+            .map_err(|e| format!("Failed to read commit history: {}", e.message()))?;
+        for query in std::mem::take(&mut config.co_authors) {
+            let trailer = resolve_co_author(&query, &authors).ok_or_else(|| {
+                format!(
+                    "No recent author matches '{}'; pass \"Name <email>\" instead",
+                    query
+                )
+            })?;
+            config.trailers.push(trailer.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// `Co-authored-by` trailers crediting everyone who worked on a squashed
+/// branch: each distinct commit author plus the co-authors those commits
+/// already credited, deduplicated by email, in order of first appearance.
+/// `committer_email` (whoever makes the squash commit) is left out.
+pub fn squash_co_authors(commits: &[BranchCommit], committer_email: Option<&str>) -> Vec<Trailer> {
+    let email_of = |value: &str| {
+        value
+            .rsplit_once('<')
+            .and_then(|(_, rest)| rest.strip_suffix('>'))
+            .unwrap_or(value)
+            .trim()
+            .to_lowercase()
+    };
+
+    let mut seen: Vec<String> = committer_email
+        .map(|email| vec![email.to_lowercase()])
+        .unwrap_or_default();
+    let mut trailers = Vec::new();
+    for commit in commits {
+        let author = format!("{} <{}>", commit.author_name, commit.author_email);
+        let credited = commit
+            .message
+            .lines()
+            .filter_map(Trailer::parse)
+            .filter(|t| t.key.eq_ignore_ascii_case("Co-authored-by"))
+            .map(|t| t.value);
+        for value in std::iter::once(author).chain(credited) {
+            let email = email_of(&value);
+            if !email.is_empty() && !seen.contains(&email) {
+                seen.push(email);
+                trailers.push(Trailer::new("Co-authored-by", &value));
+            }
+        }
+    }
+    trailers
+}
+
+/// Parse configured `Key: value` trailer strings.
+pub fn parse_all(trailers: &[String]) -> Result<Vec<Trailer>, String> {
+    trailers
+        .iter()
+        .map(|t| {
+            Trailer::parse(t)
+                .ok_or_else(|| format!("Invalid trailer '{}': expected \"Key: value\"", t))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(key: &str, value: &str) -> Trailer {
+        Trailer::new(key, value)
+    }
+
+    #[test]
+    fn test_parse_trailer() {
+        assert_eq!(
+            Trailer::parse("Reviewed-by: Ada <ada@example.com>"),
+            Some(t("Reviewed-by", "Ada <ada@example.com>"))
+        );
+        assert!(Trailer::parse("not a trailer").is_none());
+        assert!(Trailer::parse("Two words: value").is_none());
+        assert!(Trailer::parse("Key:").is_none());
+        assert!(Trailer::parse("https://example.com/docs").is_none());
+        assert!(Trailer::parse("2024: a year").is_none());
+    }
+
+    #[test]
+    fn test_append_to_subject_only_message() {
+        // "fix: ..." must not be mistaken for an existing trailer block.
+        let out = append_trailers("fix: handle empty input", &[t("Signed-off-by", "A <a@x>")]);
+        assert_eq!(out, "fix: handle empty input\n\nSigned-off-by: A <a@x>");
+    }
+
+    #[test]
+    fn test_append_joins_existing_block_and_dedupes() {
+        let msg = "feat: add login\n\n- add endpoint\n\nRefs: #12\nSigned-off-by: A <a@x>\n";
+        let out = append_trailers(
+            msg,
+            &[
+                t("signed-off-by", "A <a@x>"),
+                t("Co-authored-by", "B <b@x>"),
+                t("Co-authored-by", "B <b@x>"),
+            ],
+        );
+        assert_eq!(
+            out,
+            "feat: add login\n\n- add endpoint\n\nRefs: #12\nSigned-off-by: A <a@x>\nCo-authored-by: B <b@x>"
+        );
+    }
+
+    #[test]
+    fn test_append_drops_duplicates_already_in_the_block() {
+        let msg =
+            "fix: retry\n\nSigned-off-by: A <a@x>\nRefs: #1\n  and #2\nsigned-off-by: A <a@x>\n";
+        assert_eq!(
+            append_trailers(msg, &[]),
+            "fix: retry\n\nSigned-off-by: A <a@x>\nRefs: #1\n  and #2"
+        );
+        assert_eq!(
+            append_trailers(msg, &[t("Acked-by", "C <c@x>")]),
+            "fix: retry\n\nSigned-off-by: A <a@x>\nRefs: #1\n  and #2\nAcked-by: C <c@x>"
+        );
+    }
+
+    #[test]
+    fn test_prose_last_paragraph_is_not_a_trailer_block() {
+        let msg = "docs: link the guide\n\nSee: the guide\nit explains the setup";
+        assert_eq!(
+            append_trailers(msg, &[t("Signed-off-by", "A <a@x>")]),
+            format!("{msg}\n\nSigned-off-by: A <a@x>")
+        );
+        let url = "docs: link the guide\n\nhttps://example.com/guide";
+        assert_eq!(
+            append_trailers(url, &[t("Signed-off-by", "A <a@x>")]),
+            format!("{url}\n\nSigned-off-by: A <a@x>")
+        );
+    }
+
+    #[test]
+    fn test_append_after_body_starts_new_block() {
+        let out = append_trailers(
+            "feat: add login\n\n- add endpoint",
+            &[t("Change-Id", "I0123abcd")],
+        );
+        assert_eq!(
+            out,
+            "feat: add login\n\n- add endpoint\n\nChange-Id: I0123abcd"
+        );
+    }
+
+    #[test]
+    fn test_resolve_co_author() {
+        let authors = vec![
+            ("Ada Lovelace".to_string(), "ada@example.com".to_string()),
+            ("Grace Hopper".to_string(), "grace@example.com".to_string()),
+        ];
+        assert_eq!(
+            resolve_co_author("grace", &authors),
+            Some(t("Co-authored-by", "Grace Hopper <grace@example.com>"))
+        );
+        assert_eq!(
+            resolve_co_author("Alan <alan@example.com>", &authors),
+            Some(t("Co-authored-by", "Alan <alan@example.com>"))
+        );
+        assert!(resolve_co_author("nobody", &authors).is_none());
+    }
+
+    #[test]
+    fn test_squash_co_authors() {
+        let commit = |message: &str, name: &str, email: &str| BranchCommit {
+            id: "abc1234".to_string(),
+            message: message.to_string(),
+            author_name: name.to_string(),
+            author_email: email.to_string(),
+        };
+        let commits = vec![
+            commit("feat: start", "Me", "me@example.com"),
+            commit("fix typo", "Grace", "grace@example.com"),
+            commit(
+                "wip\n\nCo-authored-by: Alan <ALAN@example.com>",
+                "Grace",
+                "Grace@Example.com",
+            ),
+            commit("more", "Alan", "alan@example.com"),
+        ];
+
+        assert_eq!(
+            squash_co_authors(&commits, Some("ME@example.com")),
+            vec![
+                t("Co-authored-by", "Grace <grace@example.com>"),
+                t("Co-authored-by", "Alan <ALAN@example.com>"),
+            ]
+        );
+        assert_eq!(squash_co_authors(&commits, None).len(), 3);
+    }
+}